@@ -23,6 +23,10 @@
 ///   the 60 FPS budget (~16.67 ms) the benchmark marks the threshold as
 ///   breached and exits after reporting.
 /// - Results are printed after every wave and in a final summary table.
+/// - The number of live tokio tasks is reported alongside each wave. Every
+///   connection runs itself and all of its streams on a single task, so this
+///   should grow by about two per client (one per endpoint of the connection)
+///   rather than one per stream.
 ///
 /// ## Usage
 ///
@@ -140,6 +144,8 @@ struct WaveResult {
     min_fps: f64,
     avg_fps: f64,
     rx_avg: u64,
+    /// Live tokio tasks when the wave was recorded.
+    tasks: usize,
     ok: bool,
 }
 
@@ -396,7 +402,7 @@ fn client_send(
 
 // ─── Server receive (fixed tick) ─────────────────────────────────────────────
 //
-// Server-side streams are automatically accepted by SimpleServerAcceptorPlugin
+// Server-side streams are automatically accepted by StreamAcceptorPlugin
 // and tagged with QuicServerMarker (see server/acceptor.rs).
 
 fn server_recv(
@@ -411,7 +417,7 @@ fn server_recv(
         }
 
         let mut buf = Vec::new();
-        let count = stream.recv_many(&mut buf, 512);
+        let count = stream.try_recv_many(&mut buf, 512);
         let bytes: u64 = buf.iter().map(|p| p.payload.len() as u64).sum();
 
         state.bytes_this_frame += bytes;
@@ -427,6 +433,7 @@ fn server_recv(
 
 fn record_wave(
     time: Res<Time>,
+    runtime: Res<TokioRuntime>,
     mut state: ResMut<StressBench>,
     mut exit: MessageWriter<AppExit>,
 ) {
@@ -472,16 +479,18 @@ fn record_wave(
                 min_fps,
                 avg_fps,
                 rx_avg: rx / (samples.len().max(1) as u64),
+                tasks: runtime.metrics().num_alive_tasks(),
                 ok,
             };
 
             let status = if ok { "✓ OK" } else { "✗ FAIL" };
             info!(
                 "[Run {:>2}]  clients={:>4}  avg={avg_ms:.2}ms  max={max_ms:.2}ms  \
-                 avg_fps={avg_fps:.1}  min_fps={min_fps:.1}  rx={}/frame  [{status}]",
+                 avg_fps={avg_fps:.1}  min_fps={min_fps:.1}  rx={}/frame  tasks={}  [{status}]",
                 state.results.len() + 1,
                 result.client_count,
                 fmt_bytes(result.rx_avg),
+                result.tasks,
             );
 
             state.results.push(result);
@@ -526,17 +535,19 @@ fn record_wave(
                 min_fps: 1000.0 / max_ms,
                 avg_fps,
                 rx_avg: rx / (samples.len().max(1) as u64),
+                tasks: runtime.metrics().num_alive_tasks(),
                 ok: avg_ms < FPS_THRESHOLD_MS,
             };
 
             let status = if result.ok { "✓ OK" } else { "✗ FAIL" };
             info!(
                 "[Run {:>2}]  clients={:>4}  avg={avg_ms:.2}ms  max={max_ms:.2}ms  \
-                     avg_fps={avg_fps:.1}  min_fps={:.1}  rx={}/frame  [{status}]",
+                     avg_fps={avg_fps:.1}  min_fps={:.1}  rx={}/frame  tasks={}  [{status}]",
                 state.results.len() + 1,
                 result.client_count,
                 fmt_bytes(result.rx_avg),
                 1000.0 / max_ms,
+                result.tasks,
             );
 
             state.results.push(result);
@@ -574,10 +585,10 @@ fn print_report(state: &StressBench) {
 
     let _ = writeln!(
         out,
-        "  {:<5} {:<7} {:<8} {:<8} {:<8} {:<10} Status",
-        "Run", "Clients", "Avg FPS", "Min FPS", "Max ms", "RX/frame"
+        "  {:<5} {:<7} {:<8} {:<8} {:<8} {:<10} {:<7} Status",
+        "Run", "Clients", "Avg FPS", "Min FPS", "Max ms", "RX/frame", "Tasks"
     );
-    let _ = writeln!(out, "  {}", "─".repeat(58));
+    let _ = writeln!(out, "  {}", "─".repeat(66));
 
     let mut max_ok = 0usize;
 
@@ -589,19 +600,20 @@ fn print_report(state: &StressBench) {
         };
         let _ = writeln!(
             out,
-            "  {i:<5} {:<7} {:<8.1} {:<8.1} {:<8.2} {:<10} {s}",
+            "  {i:<5} {:<7} {:<8.1} {:<8.1} {:<8.2} {:<10} {:<7} {s}",
             r.client_count,
             r.avg_fps,
             r.min_fps,
             r.max_ms,
-            fmt_bytes(r.rx_avg)
+            fmt_bytes(r.rx_avg),
+            r.tasks
         );
         if r.ok {
             max_ok = r.client_count;
         }
     }
 
-    let _ = writeln!(out, "  {}", "─".repeat(58));
+    let _ = writeln!(out, "  {}", "─".repeat(66));
     let _ = writeln!(out, "  Max concurrent clients at ≥60 FPS: {max_ok}");
 
     if let Some(first_fail_index) = state.first_fail_index {
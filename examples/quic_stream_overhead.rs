@@ -0,0 +1,157 @@
+/// # QUIC Stream Overhead
+///
+/// Measures what every stream costs on the bevy side of this crate: the heap
+/// memory held per stream and the number of tokio tasks, once a connection
+/// has `STREAM_COUNT` open bidirectional streams.
+///
+/// The raw s2n-quic streams are opened before measuring, so only the
+/// [QuicReceiveStream]s and [QuicSendStream]s wrapping them are counted.
+///
+/// ## Results
+///
+/// Measured with a release build on Linux, per bidirectional stream:
+///
+/// | Version                                   | Heap     | Allocations | Tasks |
+/// |-------------------------------------------|----------|-------------|-------|
+/// | A task and three channels per stream half | 27600 B  | 31          | 2     |
+/// | Shared orchestrator, three channels       | 24400 B  | 21          | 0     |
+/// | Shared orchestrator, one control link     | 20400 B  | 15          | 0     |
+///
+/// ## Usage
+///
+/// ```ignore
+/// cargo run --example quic_stream_overhead --release
+/// ```
+use bevy_s2n_quic::common::{
+    QuicParentId, QuicParentType,
+    orchestrator::AsyncOrchestrator,
+    stream::{receive::QuicReceiveStream, send::QuicSendStream},
+};
+use s2n_quic::{Client, Server, client::Connect, provider::tls::default as tls};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    error::Error,
+    sync::atomic::{AtomicIsize, Ordering},
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+
+/// How many bidirectional streams are opened on the connection, s2n-quic lets the
+/// peer open 100 at once by default.
+const STREAM_COUNT: usize = 100;
+
+/// Keeps track of the heap memory currently in use.
+struct CountingAllocator;
+
+static ALLOCATED_BYTES: AtomicIsize = AtomicIsize::new(0);
+static ALLOCATIONS: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size() as isize, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED_BYTES.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn snapshot() -> (isize, isize) {
+    (
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+        ALLOCATIONS.load(Ordering::Relaxed),
+    )
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let identity = rcgen::generate_simple_self_signed(["localhost".to_string()])?;
+    let cert = identity.cert.pem();
+    let key = identity.signing_key.serialize_pem();
+
+    let runtime = Runtime::new()?;
+
+    let (server, client, mut client_connection, server_connection) =
+        runtime.block_on(async {
+            let server_tls = tls::Server::builder()
+                .with_certificate(cert.as_str(), key.as_str())?
+                .build()?;
+            let mut server = Server::builder()
+                .with_tls(server_tls)?
+                .with_io("127.0.0.1:0")?
+                .start()?;
+
+            let client_tls = tls::Client::builder()
+                .with_certificate(cert.as_str())?
+                .build()?;
+            let client = Client::builder()
+                .with_tls(client_tls)?
+                .with_io("127.0.0.1:0")?
+                .start()?;
+
+            let connect =
+                Connect::new(server.local_addr()?).with_server_name("localhost");
+            let (client_connection, server_connection) =
+                tokio::join!(client.connect(connect), server.accept());
+
+            Ok::<_, Box<dyn Error>>((
+                server,
+                client,
+                client_connection?,
+                server_connection.ok_or("Server closed")?,
+            ))
+        })?;
+
+    let mut raw_streams = runtime.block_on(async {
+        let mut streams = Vec::with_capacity(STREAM_COUNT);
+
+        for _ in 0..STREAM_COUNT {
+            streams.push(client_connection.open_bidirectional_stream().await?.split());
+        }
+
+        Ok::<_, Box<dyn Error>>(streams)
+    })?;
+
+    let parent_id = QuicParentId::generate_unique(QuicParentType::Client);
+    let orchestrator = AsyncOrchestrator::new(runtime.handle().clone());
+    let metrics = runtime.metrics();
+
+    let tasks_before = metrics.num_alive_tasks();
+    let (bytes_before, allocations_before) = snapshot();
+
+    // Draining keeps the buffer of the raw streams around, so freeing it isn't measured
+    let streams: Vec<_> = raw_streams
+        .drain(..)
+        .map(|(receive, send)| {
+            (
+                QuicReceiveStream::new(&orchestrator, receive, parent_id),
+                QuicSendStream::new(&orchestrator, send, parent_id),
+            )
+        })
+        .collect();
+
+    // Let every stream task start and settle
+    std::thread::sleep(Duration::from_millis(500));
+
+    let tasks_after = metrics.num_alive_tasks();
+    let (bytes_after, allocations_after) = snapshot();
+
+    println!("Bidirectional streams: {STREAM_COUNT}");
+    println!(
+        "Heap per stream:        {} bytes in {:.1} allocations",
+        (bytes_after - bytes_before) / STREAM_COUNT as isize,
+        (allocations_after - allocations_before) as f64 / STREAM_COUNT as f64,
+    );
+    println!("Tokio tasks:            {tasks_before} -> {tasks_after}");
+
+    drop((streams, raw_streams));
+    drop((server_connection, client_connection, server, client));
+
+    Ok(())
+}
@@ -27,6 +27,7 @@ use bevy_s2n_quic::{
     client::{QuicClient, marker::QuicClientMarker},
     common::{
         connection::QuicConnection,
+        error_code::QuicErrorCode,
         runtime::TokioRuntime,
         stream::{receive::QuicReceiveStream, send::QuicSendStream},
     },
@@ -226,12 +227,12 @@ fn debug_send(
 
 fn stop_receive(receivers: Query<&mut QuicReceiveStream, With<QuicServerMarker>>) {
     for mut stream in receivers {
-        stream.stop_send(0u8.into());
+        let _ = stream.stop_send(QuicErrorCode::NO_ERROR);
     }
 }
 
 fn close_send(senders: Query<&mut QuicSendStream, With<QuicClientMarker>>) {
     for mut stream in senders {
-        stream.close();
+        let _ = stream.close();
     }
 }
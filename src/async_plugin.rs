@@ -1,16 +1,39 @@
-use bevy::app::Plugin;
+use bevy::{
+    app::{First, Plugin},
+    ecs::{
+        resource::Resource,
+        system::{Res, ResMut},
+    },
+};
+use std::time::{Duration, Instant};
 
-use crate::common::runtime::TokioRuntime;
+use crate::common::runtime::{QuicRuntimeConfig, TokioRuntime};
 
 pub const DEFAULT_TICK_RATE: u16 = 64;
 
+/// Sets up the [TokioRuntime] and the [QuicTick] which paces the sync side.
+///
+/// The tick rate is how many times per second the sync systems poll the async side for
+/// finished attempts, new connections and streams, disconnects, stats and events.
+/// A tick rate of `0` polls every frame. Sending and receiving data is not paced,
+/// stream and datagram IO always happens every frame.
+///
+/// If a [TokioRuntime] resource has already been inserted it's used as is.
 pub struct QuicAsyncPlugin {
     tick_rate: u16,
+    runtime: QuicRuntimeConfig,
 }
 
 impl Plugin for QuicAsyncPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.init_resource::<TokioRuntime>();
+        if !app.world().contains_resource::<TokioRuntime>() {
+            let runtime = TokioRuntime::new(self.runtime.clone())
+                .expect("Unable to create async runtime.");
+            app.insert_resource(runtime);
+        }
+
+        app.insert_resource(QuicTick::new(self.tick_rate))
+            .add_systems(First, advance_quic_tick);
     }
 }
 
@@ -18,6 +41,7 @@ impl Default for QuicAsyncPlugin {
     fn default() -> Self {
         Self {
             tick_rate: DEFAULT_TICK_RATE,
+            runtime: QuicRuntimeConfig::default(),
         }
     }
 }
@@ -28,6 +52,76 @@ impl QuicAsyncPlugin {
     }
 
     pub fn new(tick_rate: u16) -> Self {
-        Self { tick_rate }
+        Self {
+            tick_rate,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the runtime the plugin creates, or the host runtime it uses.
+    pub fn with_runtime(mut self, runtime: QuicRuntimeConfig) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    pub fn runtime(&self) -> &QuicRuntimeConfig {
+        &self.runtime
+    }
+}
+
+/// Tracks whether the sync systems should poll the async side this frame,
+/// see [QuicAsyncPlugin] for what is paced by the tick rate.
+#[derive(Resource, Debug)]
+pub struct QuicTick {
+    interval: Duration,
+    next_tick: Instant,
+    ready: bool,
+}
+
+impl QuicTick {
+    pub fn new(tick_rate: u16) -> Self {
+        let interval = match tick_rate {
+            0 => Duration::ZERO,
+            tick_rate => Duration::from_secs(1) / tick_rate as u32,
+        };
+
+        Self {
+            interval,
+            next_tick: Instant::now(),
+            ready: true,
+        }
+    }
+
+    /// Returns `true` if this frame is a tick.
+    pub fn is_ready(&self) -> bool {
+        self.ready
     }
+
+    /// The time between ticks, zero if every frame is a tick.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+fn advance_quic_tick(mut tick: ResMut<QuicTick>) {
+    let now = Instant::now();
+    tick.ready = now >= tick.next_tick;
+
+    if !tick.ready {
+        return;
+    }
+
+    let interval = tick.interval;
+    tick.next_tick += interval;
+
+    // Skip ticks missed during a long frame rather than running them back to back.
+    if tick.next_tick <= now {
+        tick.next_tick = now + interval;
+    }
+}
+
+/// Run condition for systems paced by the [QuicTick]. Always runs
+/// if the [QuicAsyncPlugin] isn't added.
+pub fn quic_tick_ready(tick: Option<Res<QuicTick>>) -> bool {
+    tick.is_none_or(|tick| tick.is_ready())
 }
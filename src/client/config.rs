@@ -0,0 +1,110 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use s2n_quic_tls::{
+    certificate::{Certificate, IntoCertificate, IntoPrivateKey, PrivateKey},
+    error::Error as TlsError,
+};
+
+use crate::common::config::QuicTransportConfig;
+
+/// The configuration used to create a [QuicClient][crate::client::QuicClient]
+/// via [QuicClient::with_config][crate::client::QuicClient::with_config()].
+///
+/// ```no_run
+/// # use bevy_s2n_quic::client::config::QuicClientConfig;
+/// # use bevy_s2n_quic::common::config::QuicTransportConfig;
+/// # use std::{path::Path, time::Duration};
+/// let config = QuicClientConfig::default()
+///     .with_local_addr("[::]:7778".parse().unwrap())
+///     .with_trusted_certificate(Path::new("cert.pem"))
+///     .expect("Invalid certificate")
+///     .with_transport(
+///         QuicTransportConfig::default()
+///             .with_application_protocols(["my-game/1"])
+///             .with_max_idle_timeout(Duration::from_secs(10)),
+///     );
+/// ```
+pub struct QuicClientConfig {
+    local_addr: SocketAddr,
+    trusted_certificate: Option<Certificate>,
+    client_identity: Option<(Certificate, PrivateKey)>,
+    transport: QuicTransportConfig,
+}
+
+impl Default for QuicClientConfig {
+    fn default() -> Self {
+        Self {
+            local_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            trusted_certificate: None,
+            client_identity: None,
+            transport: QuicTransportConfig::default(),
+        }
+    }
+}
+
+impl QuicClientConfig {
+    /// Sets the local address the client socket binds to, defaults to `0.0.0.0:0`.
+    ///
+    /// Use an IPv6 address such as `[::]:0` to connect to servers over IPv6,
+    /// or a fixed port when firewall rules need a known source port.
+    pub fn with_local_addr(mut self, local_addr: SocketAddr) -> Self {
+        self.local_addr = local_addr;
+        self
+    }
+
+    /// Trusts the given certificate when verifying servers. This is commonly used for
+    /// development purposes to allow self-signed certs.
+    ///
+    /// Without a trusted certificate the system trust store is used.
+    pub fn with_trusted_certificate<C: IntoCertificate>(
+        mut self,
+        certificate: C,
+    ) -> Result<Self, TlsError> {
+        self.trusted_certificate = Some(certificate.into_certificate()?);
+        Ok(self)
+    }
+
+    /// Presents the given certificate to servers which require client authentication
+    /// (mutual TLS).
+    pub fn with_client_identity<C: IntoCertificate, PK: IntoPrivateKey>(
+        mut self,
+        certificate: C,
+        private_key: PK,
+    ) -> Result<Self, TlsError> {
+        self.client_identity = Some((
+            certificate.into_certificate()?,
+            private_key.into_private_key()?,
+        ));
+        Ok(self)
+    }
+
+    /// Sets the transport settings used by every connection of this client.
+    pub fn with_transport(mut self, transport: QuicTransportConfig) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn transport(&self) -> &QuicTransportConfig {
+        &self.transport
+    }
+
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        SocketAddr,
+        Option<Certificate>,
+        Option<(Certificate, PrivateKey)>,
+        QuicTransportConfig,
+    ) {
+        (
+            self.local_addr,
+            self.trusted_certificate,
+            self.client_identity,
+            self.transport,
+        )
+    }
+}
@@ -3,19 +3,21 @@ use s2n_quic::{
     Client, Connection,
     client::{Connect, ConnectionAttempt},
 };
-use s2n_quic_tls::{certificate::IntoCertificate, error::Error as TlsError};
+use s2n_quic_tls::certificate::IntoCertificate;
+use std::error::Error;
 use tokio::runtime::Handle;
 
 use crate::{
-    client::marker::QuicClientMarker,
+    client::{config::QuicClientConfig, marker::QuicClientMarker},
     common::{
-        QuicParentId, QuicParentType, attempt::TaskError,
+        QuicParentId, QuicParentType, attempt::TaskError, config::start_endpoint,
         connection::QuicConnectionAttempt, runtime::TokioRuntime,
     },
 };
 
-pub mod acceptor;
+pub mod config;
 pub mod marker;
+pub mod reconnect;
 
 /// The component which represents a client connection.
 #[derive(Component)]
@@ -29,31 +31,36 @@ pub struct QuicClient {
 impl QuicClient {
     /// Construct a client with default TLS settings. This will not allow you to connect to
     /// servers with self-signed certs.
-    pub fn new(runtime: &TokioRuntime) -> Self {
-        let client = runtime.block_on(build());
-
-        Self {
-            runtime: runtime.handle().clone(),
-            client,
-            id: QuicParentId::generate_unique(QuicParentType::Client),
-        }
+    ///
+    /// All other settings use their defaults, see [QuicClient::with_config] for more control.
+    pub fn new(runtime: &TokioRuntime) -> Result<Self, Box<dyn Error>> {
+        Self::with_config(runtime, QuicClientConfig::default())
     }
 
     /// Construct a client with custom TLS settings. This is commonly used for development purposes
     /// to allow custom certs.
+    ///
+    /// All other settings use their defaults, see [QuicClient::with_config] for more control.
     pub fn new_with_tls<C: IntoCertificate>(
         runtime: &TokioRuntime,
         certificate: C,
-    ) -> Result<Self, TlsError> {
-        let client = runtime.block_on(build_tls(certificate))?;
+    ) -> Result<Self, Box<dyn Error>> {
+        let config = QuicClientConfig::default().with_trusted_certificate(certificate)?;
+        Self::with_config(runtime, config)
+    }
+
+    /// Construct a client from the given [QuicClientConfig].
+    pub fn with_config(
+        runtime: &TokioRuntime,
+        config: QuicClientConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let client = runtime.block_on(build(config))?;
 
-        let ret = Self {
+        Ok(Self {
             runtime: runtime.handle().clone(),
             client,
             id: QuicParentId::generate_unique(QuicParentType::Client),
-        };
-
-        Ok(ret)
+        })
     }
 
     /// The unique ID for this QUIC session.
@@ -83,26 +90,30 @@ async fn create_connection(attempt: ConnectionAttempt) -> Result<Connection, Tas
     attempt.await.map_err(TaskError::ConnectionFailed)
 }
 
-async fn build() -> Client {
-    Client::builder()
-        .with_io("0.0.0.0:0")
-        .expect("Unable to build client... are we... out of sockets??")
-        .start()
-        .expect("Unable to start client")
-}
+async fn build(config: QuicClientConfig) -> Result<Client, Box<dyn Error>> {
+    let (local_addr, trusted_certificate, client_identity, transport) =
+        config.into_parts();
+
+    let mut tls = s2n_quic_tls::Client::builder();
+
+    if let Some(certificate) = trusted_certificate {
+        tls = tls.with_certificate(certificate)?;
+    }
 
-async fn build_tls<C: IntoCertificate>(certificate: C) -> Result<Client, TlsError> {
-    let tls = s2n_quic_tls::Client::builder()
-        .with_certificate(certificate)?
-        .build()?;
+    if let Some((certificate, private_key)) = client_identity {
+        tls = tls.with_client_identity(certificate, private_key)?;
+    }
+
+    if !transport.application_protocols().is_empty() {
+        tls = tls.with_application_protocols(transport.application_protocols())?;
+    }
 
-    let client = Client::builder()
-        .with_io("0.0.0.0:0")
-        .expect("Unable to build client... are we... out of sockets??")
-        .with_tls(tls)
-        .expect("Invalid TLS")
-        .start()
-        .expect("Unable to start client");
+    let builder = Client::builder()
+        .with_tls(tls.build()?)?
+        .with_io(local_addr)?
+        .with_limits(transport.build_limits()?)?
+        .with_event(transport.build_event_subscriber())?;
 
+    let client = start_endpoint!(builder, &transport)?;
     Ok(client)
 }
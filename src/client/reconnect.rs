@@ -0,0 +1,262 @@
+use aeronet_io::connection::{DisconnectReason, Disconnected};
+use bevy::{
+    app::{Plugin, Startup, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EntityEvent,
+        hierarchy::ChildOf,
+        observer::On,
+        query::Has,
+        system::{Commands, Query},
+        world::World,
+    },
+    log::{info, tracing, warn},
+};
+use s2n_quic::client::Connect;
+use std::time::{Duration, Instant};
+
+use crate::{
+    client::QuicClient,
+    common::connection::{QuicConnection, QuicConnectionAttempt},
+};
+
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+pub const DEFAULT_BACKOFF_MULTIPLIER: f32 = 2.0;
+pub const DEFAULT_BACKOFF_JITTER: f32 = 0.5;
+
+/// Add this component to a client connection entity to reconnect to the same
+/// [Connect] target whenever the connection is lost or a connection attempt fails.
+///
+/// The connection entity must be a child of its [QuicClient]. New attempts are put on
+/// the same entity, so any game state attached to it survives the reconnect.
+/// Connections closed by the local user are not reconnected.
+///
+/// Each retry waits an exponentially growing backoff, shortened by a random
+/// jitter so a server restart doesn't get hit by every client at once.
+#[derive(Component, Debug, Clone)]
+pub struct QuicReconnectPolicy {
+    connect: Connect,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f32,
+    jitter: f32,
+    max_attempts: Option<u32>,
+    attempts: u32,
+    state: ReconnectState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReconnectState {
+    /// Connected or connecting, nothing to do until the connection goes away.
+    Active,
+    Waiting(Instant),
+    /// The connection was closed locally or ran out of attempts.
+    Stopped,
+}
+
+impl QuicReconnectPolicy {
+    pub fn new(connect: Connect) -> Self {
+        Self {
+            connect,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            multiplier: DEFAULT_BACKOFF_MULTIPLIER,
+            jitter: DEFAULT_BACKOFF_JITTER,
+            max_attempts: None,
+            attempts: 0,
+            state: ReconnectState::Active,
+        }
+    }
+
+    /// Sets how long to wait before the first retry, defaults to [DEFAULT_INITIAL_BACKOFF].
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the longest time to wait between retries, defaults to [DEFAULT_MAX_BACKOFF].
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Sets how much the backoff grows after each failed retry, defaults to [DEFAULT_BACKOFF_MULTIPLIER].
+    pub fn with_multiplier(mut self, multiplier: f32) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Sets the largest fraction, from `0.0` to `1.0`, that each backoff is randomly
+    /// shortened by. Defaults to [DEFAULT_BACKOFF_JITTER].
+    pub fn with_jitter(mut self, jitter: f32) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Gives up after the given number of retries without a successful connection.
+    /// There is no limit by default.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn connect(&self) -> &Connect {
+        &self.connect
+    }
+
+    /// How many retries have been made since the last successful connection.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns `true` if the policy has given up, or the connection was closed locally.
+    pub fn is_stopped(&self) -> bool {
+        self.state == ReconnectState::Stopped
+    }
+
+    /// Resumes reconnecting after the policy has stopped, starting from the initial backoff.
+    pub fn restart(&mut self) {
+        self.attempts = 0;
+        self.state = ReconnectState::Active;
+    }
+
+    fn backoff(&self) -> Duration {
+        let exponent = self.attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self
+            .initial_backoff
+            .mul_f32(self.multiplier.powi(exponent))
+            .min(self.max_backoff);
+
+        backoff.mul_f32(1.0 - self.jitter * fastrand::f32())
+    }
+}
+
+/// Triggered on a connection entity when a reconnect has been scheduled.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicReconnecting {
+    pub entity: Entity,
+    /// The number of this retry since the last successful connection, starting at 1.
+    pub attempt: u32,
+    /// How long until the new connection attempt is made.
+    pub delay: Duration,
+}
+
+/// Triggered on a connection entity when its [QuicReconnectPolicy] has run out of attempts.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicReconnectFailed {
+    pub entity: Entity,
+    pub attempts: u32,
+}
+
+/// The plugin which drives every [QuicReconnectPolicy].
+#[derive(Debug)]
+pub struct QuicReconnectPlugin;
+
+impl Plugin for QuicReconnectPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app.add_systems(Update, handle_reconnects)
+            .add_systems(Startup, add_user_disconnect_handler);
+    }
+}
+
+type ReconnectQueryData = (
+    Entity,
+    &'static mut QuicReconnectPolicy,
+    Has<QuicConnection>,
+    Has<QuicConnectionAttempt>,
+    Option<&'static ChildOf>,
+);
+
+#[tracing::instrument(skip_all)]
+fn handle_reconnects(
+    mut commands: Commands,
+    query: Query<ReconnectQueryData>,
+    mut clients: Query<&mut QuicClient>,
+) {
+    for (entity, mut policy, connected, connecting, parent) in query {
+        if connected {
+            if policy.attempts != 0 {
+                policy.attempts = 0;
+            }
+            continue;
+        }
+
+        if connecting {
+            continue;
+        }
+
+        match policy.state {
+            ReconnectState::Stopped => continue,
+            ReconnectState::Active => {
+                if policy
+                    .max_attempts
+                    .is_some_and(|max_attempts| policy.attempts >= max_attempts)
+                {
+                    warn!(
+                        "Giving up reconnecting {entity} after {} attempts",
+                        policy.attempts
+                    );
+                    policy.state = ReconnectState::Stopped;
+                    commands.trigger(QuicReconnectFailed {
+                        entity,
+                        attempts: policy.attempts,
+                    });
+                    continue;
+                }
+
+                policy.attempts += 1;
+                let delay = policy.backoff();
+                policy.state = ReconnectState::Waiting(Instant::now() + delay);
+
+                info!(
+                    "Reconnecting {entity} to {} in {:?}, attempt {}",
+                    policy.connect, delay, policy.attempts
+                );
+                commands.trigger(QuicReconnecting {
+                    entity,
+                    attempt: policy.attempts,
+                    delay,
+                });
+            }
+            ReconnectState::Waiting(retry_at) => {
+                if Instant::now() < retry_at {
+                    continue;
+                }
+
+                let Some(mut client) =
+                    parent.and_then(|parent| clients.get_mut(parent.parent()).ok())
+                else {
+                    warn!(
+                        "Unable to reconnect {entity}, the connection entity isn't a child of a QuicClient"
+                    );
+                    policy.state = ReconnectState::Stopped;
+                    continue;
+                };
+
+                let attempt = client.open_connection(policy.connect.clone());
+                policy.state = ReconnectState::Active;
+                commands.entity(entity).insert(attempt);
+            }
+        }
+    }
+}
+
+fn add_user_disconnect_handler(world: &mut World) {
+    world.add_observer(
+        |event: On<Disconnected>, mut query: Query<&mut QuicReconnectPolicy>| {
+            let Ok(mut policy) = query.get_mut(event.entity) else {
+                return;
+            };
+
+            if matches!(event.reason, DisconnectReason::ByUser(_)) {
+                info!(
+                    "Connection {} closed locally, it will not be reconnected",
+                    event.entity
+                );
+                policy.state = ReconnectState::Stopped;
+            }
+        },
+    );
+}
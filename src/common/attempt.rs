@@ -1,6 +1,11 @@
 use bevy::ecs::component::Component;
 use s2n_quic::connection::Error as ConnectionError;
-use std::{error::Error, sync::Arc, time::SystemTime};
+use s2n_quic::stream::Error as StreamError;
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 use thiserror::Error as ThisError;
 use tokio::{
     runtime::Handle,
@@ -18,6 +23,10 @@ use crate::common::QuicParentId;
 /// See [QuicActionAttempt] for more details.
 pub trait TaskResult<T> {
     fn resolve_result(&mut self, handle: &Handle) -> Option<Result<T, TaskError>>;
+
+    /// Stops the task from producing a result, any result it may
+    /// have produced is dropped.
+    fn abort(&mut self) {}
 }
 
 impl<T> TaskResult<T> for oneshot::Receiver<Result<T, TaskError>> {
@@ -34,6 +43,12 @@ impl<T> TaskResult<T> for oneshot::Receiver<Result<T, TaskError>> {
             },
         }
     }
+
+    /// Closes the receiver, the task answering on the sender is expected to
+    /// watch for this with [oneshot::Sender::closed()] and give up.
+    fn abort(&mut self) {
+        self.close();
+    }
 }
 
 impl<T> TaskResult<T> for JoinHandle<Result<T, TaskError>> {
@@ -49,17 +64,27 @@ impl<T> TaskResult<T> for JoinHandle<Result<T, TaskError>> {
             Err(e) => Some(Err(TaskError::TaskFailed(Arc::new(e)))),
         }
     }
+
+    fn abort(&mut self) {
+        JoinHandle::abort(self);
+    }
 }
 
 /// This is the structure which represents the async task
 /// being attempted and waited upon via sync polling with
 /// [attempt_result][QuicActionAttempt::attempt_result()]
+///
+/// The underlying task is aborted if the attempt is dropped before
+/// a result was returned, e.g. when the component is removed or
+/// its entity is despawned.
 pub struct QuicActionAttempt<T> {
     runtime: Handle,
     task_res: Box<dyn TaskResult<T> + Send + Sync>,
     /// A flag checking if the action state has returned a success value already
     returned_value: Option<QuicActionError>,
     parent_id: QuicParentId,
+    started_at: Instant,
+    timeout: Option<Duration>,
 }
 
 impl<T> QuicActionAttempt<T> {
@@ -73,7 +98,30 @@ impl<T> QuicActionAttempt<T> {
             task_res: Box::new(task),
             returned_value: None,
             parent_id,
+            started_at: Instant::now(),
+            timeout: None,
+        }
+    }
+
+    /// Sets how long the attempt may stay pending, measured from when the attempt was created.
+    /// Once exceeded the task is aborted and the attempt fails with [QuicActionError::TimedOut].
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Aborts the underlying task, the attempt will fail with [QuicActionError::Cancelled].
+    /// Does nothing if the attempt has already returned a result.
+    pub fn cancel(&mut self) {
+        if self.returned_value.is_some() {
+            return;
         }
+
+        self.task_res.abort();
+        self.returned_value = Some(QuicActionError::Cancelled);
     }
 
     /// Attempt to get the result
@@ -85,6 +133,14 @@ impl<T> QuicActionAttempt<T> {
         let value = self.task_res.resolve_result(&self.runtime);
 
         let Some(res) = value else {
+            if let Some(timeout) = self.timeout
+                && self.started_at.elapsed() >= timeout
+            {
+                self.task_res.abort();
+                self.returned_value = Some(QuicActionError::TimedOut(timeout));
+                return Err(QuicActionError::TimedOut(timeout));
+            }
+
             return Err(QuicActionError::Pending);
         };
 
@@ -102,6 +158,10 @@ impl<T> QuicActionAttempt<T> {
                     self.returned_value = Some(QuicActionError::Crashed(Arc::new(err)));
                     Err(self.returned_value.as_ref().unwrap().clone())
                 }
+                TaskError::StreamFailed(err) => {
+                    self.returned_value = Some(QuicActionError::StreamFailed(err));
+                    Err(self.returned_value.as_ref().unwrap().clone())
+                }
             },
         }
     }
@@ -111,6 +171,14 @@ impl<T> QuicActionAttempt<T> {
     }
 }
 
+impl<T> Drop for QuicActionAttempt<T> {
+    fn drop(&mut self) {
+        if self.returned_value.is_none() {
+            self.task_res.abort();
+        }
+    }
+}
+
 /// An enum representing all the ways a [QuicActionAttempt] can fail.
 /// See [QuicActionErrorComponent] for more details.
 #[derive(Clone, Debug, ThisError)]
@@ -124,6 +192,16 @@ pub enum QuicActionError {
     ConnectionFailed(ConnectionError),
     #[error("Crashed: {0}")]
     Crashed(Arc<dyn std::error::Error + Send + Sync>),
+    /// The stream returned an error while carrying out a
+    /// [QuicStreamCommand][crate::common::stream::QuicStreamCommand].
+    #[error("StreamFailed: {0}")]
+    StreamFailed(StreamError),
+    /// The attempt was still pending once its timeout had passed.
+    #[error("TimedOut: no result after {0:?}")]
+    TimedOut(Duration),
+    /// The attempt was cancelled via [QuicActionAttempt::cancel()].
+    #[error("Cancelled")]
+    Cancelled,
 }
 
 /// An enum representing all the ways a [TaskResult] can fail.
@@ -135,6 +213,8 @@ pub enum TaskError {
     ConnectionFailed(ConnectionError),
     #[error("Crashed: {0}")]
     TaskFailed(Arc<dyn Error + Send + Sync>),
+    #[error("StreamFailed: {0}")]
+    StreamFailed(StreamError),
 }
 
 impl From<ConnectionError> for TaskError {
@@ -143,6 +223,12 @@ impl From<ConnectionError> for TaskError {
     }
 }
 
+impl From<StreamError> for TaskError {
+    fn from(value: StreamError) -> Self {
+        Self::StreamFailed(value)
+    }
+}
+
 /// This is a Bevy component which is added to an entity
 /// in the event a [QuicActionAttempt] fails.
 ///
@@ -0,0 +1,234 @@
+use s2n_quic::provider::{event::Subscriber, limits::Limits};
+use std::{error::Error, time::Duration};
+
+use crate::common::connection::{
+    datagram::QuicDatagramConfig,
+    event::{EventBridgeSubscriber, QuicEventFilter},
+    identity::PeerIdentitySubscriber,
+    stats::StatsSubscriber,
+};
+
+/// The congestion control algorithm used by every connection of an endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuicCongestionController {
+    /// CUBIC, loss based congestion control. This is the s2n-quic default.
+    #[default]
+    Cubic,
+    /// BBRv2, model based congestion control. Usually handles lossy
+    /// links better than CUBIC at the cost of a little more CPU.
+    Bbr,
+}
+
+/// Transport settings for the connections of a [QuicServerConfig][crate::server::config::QuicServerConfig]
+/// or a [QuicClientConfig][crate::client::config::QuicClientConfig].
+///
+/// Any setting left unset uses the s2n-quic default. Datagrams are enabled
+/// with the default [QuicDatagramConfig].
+#[derive(Debug, Clone)]
+pub struct QuicTransportConfig {
+    application_protocols: Vec<Vec<u8>>,
+    max_idle_timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    max_open_bidirectional_streams: Option<u64>,
+    max_open_unidirectional_streams: Option<u64>,
+    connection_data_window: Option<u64>,
+    stream_data_window: Option<u64>,
+    congestion_controller: QuicCongestionController,
+    datagrams: Option<QuicDatagramConfig>,
+    event_filter: QuicEventFilter,
+}
+
+impl Default for QuicTransportConfig {
+    fn default() -> Self {
+        Self {
+            application_protocols: Vec::new(),
+            max_idle_timeout: None,
+            keep_alive_interval: None,
+            max_open_bidirectional_streams: None,
+            max_open_unidirectional_streams: None,
+            connection_data_window: None,
+            stream_data_window: None,
+            congestion_controller: QuicCongestionController::default(),
+            datagrams: Some(QuicDatagramConfig::default()),
+            event_filter: QuicEventFilter::none(),
+        }
+    }
+}
+
+impl QuicTransportConfig {
+    /// Sets the ALPN protocols to negotiate, in order of preference.
+    /// Both peers must share at least one protocol for a handshake to succeed.
+    ///
+    /// When unset the s2n-quic default of `h3` is used.
+    pub fn with_application_protocols<P: IntoIterator<Item = I>, I: AsRef<[u8]>>(
+        mut self,
+        protocols: P,
+    ) -> Self {
+        self.application_protocols = protocols
+            .into_iter()
+            .map(|protocol| protocol.as_ref().to_vec())
+            .collect();
+        self
+    }
+
+    /// Sets how long a connection may go without receiving anything before it is closed.
+    pub fn with_max_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.max_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how often a keep alive ping is sent on an otherwise idle connection.
+    /// This should be smaller than the max idle timeout.
+    pub fn with_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how many bidirectional streams can be open at once per connection,
+    /// for both locally and peer opened streams.
+    pub fn with_max_open_bidirectional_streams(mut self, count: u64) -> Self {
+        self.max_open_bidirectional_streams = Some(count);
+        self
+    }
+
+    /// Sets how many unidirectional streams can be open at once per connection,
+    /// for both locally and peer opened streams.
+    pub fn with_max_open_unidirectional_streams(mut self, count: u64) -> Self {
+        self.max_open_unidirectional_streams = Some(count);
+        self
+    }
+
+    /// Sets the initial flow control window, in bytes, for a whole connection.
+    pub fn with_connection_data_window(mut self, bytes: u64) -> Self {
+        self.connection_data_window = Some(bytes);
+        self
+    }
+
+    /// Sets the initial flow control window, in bytes, for each individual stream.
+    pub fn with_stream_data_window(mut self, bytes: u64) -> Self {
+        self.stream_data_window = Some(bytes);
+        self
+    }
+
+    /// Sets the congestion control algorithm.
+    pub fn with_congestion_controller(
+        mut self,
+        congestion_controller: QuicCongestionController,
+    ) -> Self {
+        self.congestion_controller = congestion_controller;
+        self
+    }
+
+    /// Enables datagrams with the given config, or disables them with `None`.
+    pub fn with_datagrams(mut self, datagrams: Option<QuicDatagramConfig>) -> Self {
+        self.datagrams = datagrams;
+        self
+    }
+
+    /// Sets which s2n-quic events are forwarded to the ECS as
+    /// [QuicConnectionEvent][crate::common::connection::event::QuicConnectionEvent]s.
+    /// No events are forwarded by default.
+    pub fn with_event_filter(mut self, filter: QuicEventFilter) -> Self {
+        self.event_filter = filter;
+        self
+    }
+
+    pub fn application_protocols(&self) -> &[Vec<u8>] {
+        &self.application_protocols
+    }
+
+    pub fn congestion_controller(&self) -> QuicCongestionController {
+        self.congestion_controller
+    }
+
+    pub fn datagrams(&self) -> Option<QuicDatagramConfig> {
+        self.datagrams
+    }
+
+    pub fn event_filter(&self) -> QuicEventFilter {
+        self.event_filter
+    }
+
+    /// Builds the s2n-quic event subscribers every endpoint needs for its connections.
+    pub(crate) fn build_event_subscriber(&self) -> impl Subscriber + use<> {
+        (
+            PeerIdentitySubscriber,
+            (
+                StatsSubscriber,
+                EventBridgeSubscriber::new(self.event_filter),
+            ),
+        )
+    }
+
+    /// Builds the s2n-quic connection limits for this config.
+    pub(crate) fn build_limits(&self) -> Result<Limits, Box<dyn Error>> {
+        let mut limits = Limits::new();
+
+        if let Some(timeout) = self.max_idle_timeout {
+            limits = limits.with_max_idle_timeout(timeout)?;
+        }
+
+        if let Some(interval) = self.keep_alive_interval {
+            limits = limits.with_max_keep_alive_period(interval)?;
+        }
+
+        if let Some(count) = self.max_open_bidirectional_streams {
+            limits = limits
+                .with_max_open_local_bidirectional_streams(count)?
+                .with_max_open_remote_bidirectional_streams(count)?;
+        }
+
+        if let Some(count) = self.max_open_unidirectional_streams {
+            limits = limits
+                .with_max_open_local_unidirectional_streams(count)?
+                .with_max_open_remote_unidirectional_streams(count)?;
+        }
+
+        if let Some(bytes) = self.connection_data_window {
+            limits = limits.with_data_window(bytes)?;
+        }
+
+        if let Some(bytes) = self.stream_data_window {
+            limits = limits
+                .with_bidirectional_local_data_window(bytes)?
+                .with_bidirectional_remote_data_window(bytes)?
+                .with_unidirectional_data_window(bytes)?;
+        }
+
+        Ok(limits)
+    }
+}
+
+/// Installs the congestion controller and datagram providers of a
+/// [QuicTransportConfig] on an s2n-quic server or client builder and starts it.
+///
+/// Each provider changes the type of the builder, so every combination
+/// has to be spelled out.
+macro_rules! start_endpoint {
+    ($builder:expr, $transport:expr) => {{
+        use s2n_quic::provider::congestion_controller::{Bbr, Cubic};
+        use $crate::common::config::QuicCongestionController;
+
+        let builder = $builder;
+        let transport = $transport;
+
+        match (transport.congestion_controller(), transport.datagrams()) {
+            (QuicCongestionController::Cubic, Some(datagrams)) => builder
+                .with_congestion_controller(Cubic::default())?
+                .with_datagram(datagrams.build_endpoint()?)?
+                .start(),
+            (QuicCongestionController::Cubic, None) => builder
+                .with_congestion_controller(Cubic::default())?
+                .start(),
+            (QuicCongestionController::Bbr, Some(datagrams)) => builder
+                .with_congestion_controller(Bbr::default())?
+                .with_datagram(datagrams.build_endpoint()?)?
+                .start(),
+            (QuicCongestionController::Bbr, None) => {
+                builder.with_congestion_controller(Bbr::default())?.start()
+            }
+        }
+    }};
+}
+
+pub(crate) use start_endpoint;
@@ -0,0 +1,175 @@
+use bevy::log::{info, warn};
+use bytes::{Bytes, BytesMut};
+use futures::FutureExt;
+use s2n_quic::{
+    Connection, application,
+    connection::Handle as ConnectionHandle,
+    stream::{ReceiveStream, SendStream},
+};
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::common::{
+    QuicParentType, connection::disconnect::MAX_CLOSE_REASON_LEN,
+    error_code::QuicErrorCode,
+};
+
+/// Starts every control stream, so a peer which doesn't reserve one isn't mistaken
+/// for one that does.
+const CONTROL_STREAM_MAGIC: &[u8] = b"\0bevy-s2n-quic/control\0";
+/// How long a close waits for its reason to reach the peer before closing anyway.
+const CLOSE_REASON_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Opens the control stream of a new connection.
+///
+/// This has to happen before any other stream is opened, so the control stream is
+/// the first unidirectional stream of this side of the connection. Returns `None` if
+/// the stream can't be opened straight away, no reasons are sent to the peer then.
+pub(super) fn open_control_stream(connection: &mut Connection) -> Option<SendStream> {
+    match connection.open_send_stream().now_or_never() {
+        Some(Ok(stream)) => Some(stream),
+        Some(Err(err)) => {
+            warn!("Unable to open the control stream: {}", err);
+            None
+        }
+        None => {
+            warn!("No stream was available for the control stream");
+            None
+        }
+    }
+}
+
+/// Sends the start of the control stream, letting the peer know it can expect a close
+/// reason on it.
+pub(super) async fn start_control_stream(stream: &mut SendStream) -> bool {
+    match stream.send(Bytes::from_static(CONTROL_STREAM_MAGIC)).await {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("Unable to start the control stream: {}", err);
+            false
+        }
+    }
+}
+
+/// The ID of the control stream opened by the peer, its first unidirectional stream.
+pub(super) fn peer_control_stream_id(parent_type: QuicParentType) -> u64 {
+    // The lowest bits of a stream ID are its direction and which side opened it
+    match parent_type {
+        QuicParentType::Server => 0b10,
+        QuicParentType::Client => 0b11,
+    }
+}
+
+/// Sends the reason on the control stream and closes the connection once the peer has
+/// it, or once [CLOSE_REASON_TIMEOUT] runs out so an unresponsive peer can't hold up
+/// the close.
+///
+/// Runs as a task of its own, the connection task carries on until the close arrives.
+pub(super) async fn send_close_reason(
+    mut stream: SendStream,
+    connection: ConnectionHandle,
+    code: application::Error,
+    reason: String,
+) {
+    let send = async {
+        stream.send(Bytes::from(reason)).await?;
+        // Waits for the peer to acknowledge the reason
+        stream.close().await
+    };
+
+    match timeout(CLOSE_REASON_TIMEOUT, send).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => warn!("Unable to send close reason to peer: {}", err),
+        Err(_) => warn!("Timed out sending close reason to peer"),
+    }
+
+    connection.close(code);
+}
+
+/// What the peer sent on its control stream.
+pub(super) enum ControlEvent {
+    /// The peer sent the reason it's closing the connection for.
+    CloseReason(String),
+    /// The stream doesn't start like a control stream, it's one of the peer's own.
+    NotControl,
+    /// The stream failed or sent more than a reason can hold.
+    Closed,
+}
+
+/// The control stream opened by the peer, read by the connection task.
+#[derive(Debug)]
+pub(super) struct PeerControlStream {
+    stream: ReceiveStream,
+    received: BytesMut,
+    verified: bool,
+}
+
+impl PeerControlStream {
+    pub(super) fn new(stream: ReceiveStream) -> Self {
+        Self {
+            stream,
+            received: BytesMut::new(),
+            verified: false,
+        }
+    }
+
+    /// Reads the stream until the peer has sent something to act on.
+    ///
+    /// Cancel safe, everything received is kept for the next call.
+    pub(super) async fn next(&mut self) -> ControlEvent {
+        loop {
+            let chunk = match self.stream.receive().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) if self.verified => {
+                    let reason = String::from_utf8_lossy(&self.received).into_owned();
+                    info!("Received close reason from peer");
+                    return ControlEvent::CloseReason(reason);
+                }
+                Ok(None) => return ControlEvent::NotControl,
+                Err(err) => {
+                    warn!("Control stream of the peer failed: {}", err);
+                    return ControlEvent::Closed;
+                }
+            };
+
+            self.received.extend_from_slice(&chunk);
+
+            if !self.verified {
+                let checked = self.received.len().min(CONTROL_STREAM_MAGIC.len());
+
+                if self.received[..checked] != CONTROL_STREAM_MAGIC[..checked] {
+                    return ControlEvent::NotControl;
+                }
+
+                if checked < CONTROL_STREAM_MAGIC.len() {
+                    continue;
+                }
+
+                let _ = self.received.split_to(CONTROL_STREAM_MAGIC.len());
+                self.verified = true;
+            }
+
+            if self.received.len() > MAX_CLOSE_REASON_LEN {
+                warn!(
+                    "Peer sent a close reason longer than {MAX_CLOSE_REASON_LEN} bytes"
+                );
+                let _ = self
+                    .stream
+                    .stop_sending(QuicErrorCode::PROTOCOL_VIOLATION.into());
+                return ControlEvent::Closed;
+            }
+        }
+    }
+
+    /// Reads whatever has already arrived, used once the connection has closed and
+    /// nothing more will.
+    pub(super) fn next_now(&mut self) -> Option<ControlEvent> {
+        self.next().now_or_never()
+    }
+
+    /// Hands the stream back as one of the peer's own, along with what was read from it.
+    pub(super) fn into_peer_stream(self) -> (ReceiveStream, Option<Bytes>) {
+        let received = (!self.received.is_empty()).then(|| self.received.freeze());
+        (self.stream, received)
+    }
+}
@@ -0,0 +1,107 @@
+use s2n_quic::{
+    connection::Error as ConnectionError,
+    provider::datagram::default::{self, BuilderError, DatagramError},
+};
+use thiserror::Error;
+
+/// The default number of datagrams which can be queued to be sent
+/// before [send_datagram][crate::common::connection::QuicConnection::send_datagram()]
+/// starts returning [QuicDatagramError::QueueFull].
+pub const DEFAULT_DATAGRAM_SEND_CAPACITY: usize = 256;
+/// The default number of received datagrams held for a connection.
+/// Once full, the oldest datagram is dropped in favour of the newest.
+pub const DEFAULT_DATAGRAM_RECV_CAPACITY: usize = 256;
+
+/// The smallest datagram payload a QUIC connection will always be able to carry.
+///
+/// This is the 1200 byte minimum QUIC packet size minus a worst case short
+/// header, AEAD tag and datagram frame header, rounded down.
+pub const MIN_DATAGRAM_MTU: usize = 1100;
+
+/// The most a datagram frame adds to its payload, a type byte and a length
+/// prefix of up to two bytes.
+pub(crate) const DATAGRAM_FRAME_OVERHEAD: usize = 3;
+
+/// Configuration for unreliable datagram support on a QUIC endpoint.
+///
+/// Datagrams are unordered and unreliable, they are never retransmitted
+/// and will never head-of-line block any other data on the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuicDatagramConfig {
+    send_capacity: usize,
+    recv_capacity: usize,
+}
+
+impl Default for QuicDatagramConfig {
+    fn default() -> Self {
+        Self {
+            send_capacity: DEFAULT_DATAGRAM_SEND_CAPACITY,
+            recv_capacity: DEFAULT_DATAGRAM_RECV_CAPACITY,
+        }
+    }
+}
+
+impl QuicDatagramConfig {
+    pub fn new(send_capacity: usize, recv_capacity: usize) -> Self {
+        Self {
+            send_capacity,
+            recv_capacity,
+        }
+    }
+
+    /// How many outgoing datagrams can be queued at once per connection.
+    pub fn send_capacity(&self) -> usize {
+        self.send_capacity
+    }
+
+    /// How many incoming datagrams can be held at once per connection.
+    pub fn recv_capacity(&self) -> usize {
+        self.recv_capacity
+    }
+
+    /// Builds the s2n-quic datagram provider for this configuration.
+    pub(crate) fn build_endpoint(&self) -> Result<default::Endpoint, BuilderError> {
+        let endpoint = default::Endpoint::builder()
+            .with_send_capacity(self.send_capacity)?
+            .with_recv_capacity(self.recv_capacity)?
+            .build()
+            .expect("Building a datagram endpoint is infallible");
+
+        Ok(endpoint)
+    }
+}
+
+/// Errors that can occur when sending a datagram over a
+/// [QuicConnection][crate::common::connection::QuicConnection].
+#[derive(Debug, Error, Clone)]
+pub enum QuicDatagramError {
+    /// The endpoint which created this connection does not have datagrams enabled.
+    #[error("Datagrams are not enabled for this connection.")]
+    Disabled,
+    /// Too many datagrams are waiting to be sent, the datagram has been dropped.
+    #[error("The datagram send queue is full.")]
+    QueueFull,
+    /// The datagram is larger than the peer is willing to receive.
+    #[error("The datagram is larger than the peer's transport limits allow.")]
+    TooLarge,
+    /// The connection has been closed or has errored.
+    #[error("Datagram could not be sent due to a connection error: {0}")]
+    ConnectionError(ConnectionError),
+    /// The connection is in an unusable state.
+    #[error("The connection lock has been poisoned.")]
+    LockPoisoned,
+    /// Any other datagram error reported by s2n-quic.
+    #[error("Datagram could not be sent: {0}")]
+    Other(String),
+}
+
+impl From<DatagramError> for QuicDatagramError {
+    fn from(value: DatagramError) -> Self {
+        match value {
+            DatagramError::QueueAtCapacity { .. } => Self::QueueFull,
+            DatagramError::ExceedsPeerTransportLimits { .. } => Self::TooLarge,
+            DatagramError::ConnectionError { error, .. } => Self::ConnectionError(error),
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
@@ -2,15 +2,27 @@ use aeronet_io::{anyhow::anyhow, connection::DisconnectReason};
 use s2n_quic::connection::{Error as ConnectionError, error::Code};
 use std::{error::Error, sync::Arc};
 
+use crate::common::error_code::QuicErrorCode;
+
 const PEER_CLOSED_WITHOUT_CODE: &str =
     "Connection has been closed by user without an error";
 
+/// The longest reason [QuicConnection::close_with_reason()][super::QuicConnection::close_with_reason()]
+/// sends to the peer, longer reasons are truncated.
+pub const MAX_CLOSE_REASON_LEN: usize = 1024;
+
 #[derive(Clone, Debug)]
 pub enum ConnectionDisconnectReason {
     /// Connection was closed by the local user explicitly
     UserClosed(Code),
     /// Connection was closed by the peer without an error
     PeerClosed,
+    /// Connection was closed by the peer with a reason, see
+    /// [QuicConnection::close_with_reason()][super::QuicConnection::close_with_reason()]
+    PeerDisconnected {
+        code: QuicErrorCode,
+        reason: String,
+    },
     /// Connection was closed or errored elsewhere
     ConnectionError(ConnectionError),
     MspcChannelClosed {
@@ -19,6 +31,20 @@ pub enum ConnectionDisconnectReason {
     InternalError(Arc<dyn Error + Send + Sync>),
 }
 
+impl ConnectionDisconnectReason {
+    /// The application error code the connection was closed with, by either side. Registered
+    /// codes can be turned back into their name with [QuicErrorCode::name()].
+    pub fn error_code(&self) -> Option<QuicErrorCode> {
+        match self {
+            Self::ConnectionError(ConnectionError::Application { error, .. }) => {
+                Some((*error).into())
+            }
+            Self::PeerDisconnected { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
 impl From<Arc<dyn Error + Send + Sync>> for ConnectionDisconnectReason {
     fn from(error: Arc<dyn Error + Send + Sync>) -> Self {
         ConnectionDisconnectReason::InternalError(error)
@@ -34,20 +60,23 @@ impl From<ConnectionDisconnectReason> for DisconnectReason {
             ConnectionDisconnectReason::PeerClosed => {
                 DisconnectReason::ByPeer(PEER_CLOSED_WITHOUT_CODE.to_owned())
             }
+            ConnectionDisconnectReason::PeerDisconnected { reason, .. } => {
+                DisconnectReason::ByPeer(reason)
+            }
             ConnectionDisconnectReason::ConnectionError(conn_err) => match conn_err {
                 s2n_quic::connection::Error::Application {
                     error, initiator, ..
-                } => match initiator {
-                    s2n_quic::provider::event::Location::Local => {
+                } => match (initiator, QuicErrorCode::from(error)) {
+                    (s2n_quic::provider::event::Location::Local, code) => {
                         DisconnectReason::ByUser(format!(
                             "Connection has been closed by user with error code: {}",
-                            error
+                            code
                         ))
                     }
-                    s2n_quic::provider::event::Location::Remote => {
+                    (s2n_quic::provider::event::Location::Remote, code) => {
                         DisconnectReason::ByPeer(format!(
                             "Connection has been closed by peer with error code: {}",
-                            error
+                            code
                         ))
                     }
                 },
@@ -0,0 +1,324 @@
+use bevy::{
+    app::{Plugin, PreUpdate},
+    ecs::{
+        entity::Entity,
+        event::EntityEvent,
+        message::{Message, MessageWriter},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query},
+    },
+    log::{tracing, warn},
+};
+use s2n_quic::provider::event::{
+    ConnectionInfo, ConnectionMeta, Subscriber,
+    events::{self, ActivePathUpdated, HandshakeStatusUpdated, KeyUpdate, PacketLost},
+};
+use std::{collections::VecDeque, net::SocketAddr, time::Instant};
+
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::connection::{QuicConnection, id::ConnectionId},
+};
+
+/// How many events are held per connection before the oldest are dropped.
+const EVENT_QUEUE_SIZE: usize = 256;
+
+/// Selects which s2n-quic events are forwarded to the ECS as [QuicConnectionEvent]s.
+///
+/// Nothing is forwarded by default, set on an endpoint through
+/// [QuicTransportConfig::with_event_filter][crate::common::config::QuicTransportConfig::with_event_filter()].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuicEventFilter {
+    handshake: bool,
+    path: bool,
+    packet_loss: bool,
+    key_update: bool,
+}
+
+impl QuicEventFilter {
+    /// Forwards no events.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Forwards every event kind.
+    pub fn all() -> Self {
+        Self {
+            handshake: true,
+            path: true,
+            packet_loss: true,
+            key_update: true,
+        }
+    }
+
+    /// Forwards [QuicEventKind::Handshake] events.
+    pub fn with_handshake(mut self) -> Self {
+        self.handshake = true;
+        self
+    }
+
+    /// Forwards [QuicEventKind::PathUpdated] events.
+    pub fn with_path(mut self) -> Self {
+        self.path = true;
+        self
+    }
+
+    /// Forwards [QuicEventKind::PacketLost] events. This can be very noisy on a lossy link.
+    pub fn with_packet_loss(mut self) -> Self {
+        self.packet_loss = true;
+        self
+    }
+
+    /// Forwards [QuicEventKind::KeyUpdated] events.
+    pub fn with_key_update(mut self) -> Self {
+        self.key_update = true;
+        self
+    }
+
+    /// Returns `true` if no events will be forwarded.
+    pub fn is_none(&self) -> bool {
+        *self == Self::none()
+    }
+}
+
+/// How far along the handshake of a connection is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuicHandshakeStatus {
+    /// The handshake has completed, application data can be sent.
+    Complete,
+    /// The handshake has been confirmed by the peer.
+    Confirmed,
+    /// The server's HANDSHAKE_DONE frame has been acknowledged by the client.
+    HandshakeDoneAcked,
+    /// The server's HANDSHAKE_DONE frame was lost and will be resent.
+    HandshakeDoneLost,
+}
+
+/// The encryption level a new set of keys was derived for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuicKeyType {
+    Initial,
+    Handshake,
+    ZeroRtt,
+    /// Application data keys, the generation increases with each key update.
+    OneRtt {
+        generation: u16,
+    },
+}
+
+/// The s2n-quic events which can be forwarded, see [QuicEventFilter].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuicEventKind {
+    Handshake(QuicHandshakeStatus),
+    /// The connection has migrated to a new network path.
+    PathUpdated {
+        previous_remote_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    },
+    PacketLost {
+        bytes_lost: u16,
+        is_mtu_probe: bool,
+    },
+    KeyUpdated(QuicKeyType),
+}
+
+/// An s2n-quic event which happened on a [QuicConnection].
+///
+/// These are both written as a [Message] and triggered on the connection entity
+/// by the [ConnectionEventPlugin], so they can be read with a `MessageReader` or
+/// observed with `On<QuicConnectionEvent>`.
+///
+/// Events which happen during the handshake are held until the connection
+/// entity exists, so they arrive all at once.
+#[derive(Debug, Clone, Message, EntityEvent)]
+pub struct QuicConnectionEvent {
+    /// The [QuicConnection] entity this event happened on.
+    pub entity: Entity,
+    pub connection_id: ConnectionId,
+    /// When the event was seen by the async side.
+    pub timestamp: Instant,
+    pub kind: QuicEventKind,
+}
+
+/// The plugin which forwards queued s2n-quic events to the ECS.
+#[derive(Debug)]
+pub struct ConnectionEventPlugin;
+
+impl Plugin for ConnectionEventPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app.add_message::<QuicConnectionEvent>()
+            .add_systems(PreUpdate, forward_connection_events.run_if(quic_tick_ready));
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn forward_connection_events(
+    mut commands: Commands,
+    mut messages: MessageWriter<QuicConnectionEvent>,
+    query: Query<(Entity, &mut QuicConnection)>,
+) {
+    let mut buffer = Vec::new();
+
+    for (entity, mut connection) in query {
+        let dropped = connection.drain_events(&mut buffer);
+
+        #[cfg(feature = "performance-warns")]
+        if dropped > 0 {
+            warn!(
+                "{dropped} events for '{}' were dropped before they could be forwarded.",
+                connection.id()
+            );
+        }
+
+        for (timestamp, kind) in buffer.drain(..) {
+            let event = QuicConnectionEvent {
+                entity,
+                connection_id: connection.id(),
+                timestamp,
+                kind,
+            };
+
+            messages.write(event.clone());
+            commands.trigger(event);
+        }
+    }
+}
+
+/// Event subscriber which queues the events selected by a [QuicEventFilter]
+/// on each connection until they are drained by the ECS.
+#[derive(Debug)]
+pub(crate) struct EventBridgeSubscriber {
+    filter: QuicEventFilter,
+}
+
+impl EventBridgeSubscriber {
+    pub(crate) fn new(filter: QuicEventFilter) -> Self {
+        Self { filter }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct EventBridgeContext {
+    events: VecDeque<(Instant, QuicEventKind)>,
+    dropped: usize,
+    /// Tracked regardless of the filter, drives [QuicConnectionState][crate::common::connection::state::QuicConnectionState]
+    handshake_confirmed: bool,
+}
+
+impl EventBridgeContext {
+    fn push(&mut self, kind: QuicEventKind) {
+        if self.events.len() >= EVENT_QUEUE_SIZE {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+
+        self.events.push_back((Instant::now(), kind));
+    }
+
+    /// Moves every queued event into the buffer, returning how many
+    /// events were dropped since the last drain.
+    pub(crate) fn drain(&mut self, buffer: &mut Vec<(Instant, QuicEventKind)>) -> usize {
+        buffer.extend(self.events.drain(..));
+        std::mem::take(&mut self.dropped)
+    }
+
+    pub(crate) fn handshake_confirmed(&self) -> bool {
+        self.handshake_confirmed
+    }
+}
+
+impl Subscriber for EventBridgeSubscriber {
+    type ConnectionContext = EventBridgeContext;
+
+    fn create_connection_context(
+        &mut self,
+        _meta: &ConnectionMeta,
+        _info: &ConnectionInfo,
+    ) -> Self::ConnectionContext {
+        EventBridgeContext::default()
+    }
+
+    fn on_handshake_status_updated(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &HandshakeStatusUpdated,
+    ) {
+        if let events::HandshakeStatus::Confirmed { .. } = event.status {
+            context.handshake_confirmed = true;
+        }
+
+        if !self.filter.handshake {
+            return;
+        }
+
+        let status = match event.status {
+            events::HandshakeStatus::Complete { .. } => QuicHandshakeStatus::Complete,
+            events::HandshakeStatus::Confirmed { .. } => QuicHandshakeStatus::Confirmed,
+            events::HandshakeStatus::HandshakeDoneAcked { .. } => {
+                QuicHandshakeStatus::HandshakeDoneAcked
+            }
+            events::HandshakeStatus::HandshakeDoneLost { .. } => {
+                QuicHandshakeStatus::HandshakeDoneLost
+            }
+            _ => return,
+        };
+
+        context.push(QuicEventKind::Handshake(status));
+    }
+
+    fn on_active_path_updated(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &ActivePathUpdated,
+    ) {
+        if !self.filter.path {
+            return;
+        }
+
+        context.push(QuicEventKind::PathUpdated {
+            previous_remote_addr: SocketAddr::from(&event.previous.remote_addr),
+            remote_addr: SocketAddr::from(&event.active.remote_addr),
+        });
+    }
+
+    fn on_packet_lost(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &PacketLost,
+    ) {
+        if !self.filter.packet_loss {
+            return;
+        }
+
+        context.push(QuicEventKind::PacketLost {
+            bytes_lost: event.bytes_lost,
+            is_mtu_probe: event.is_mtu_probe,
+        });
+    }
+
+    fn on_key_update(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &KeyUpdate,
+    ) {
+        if !self.filter.key_update {
+            return;
+        }
+
+        let key_type = match event.key_type {
+            events::KeyType::Initial { .. } => QuicKeyType::Initial,
+            events::KeyType::Handshake { .. } => QuicKeyType::Handshake,
+            events::KeyType::ZeroRtt { .. } => QuicKeyType::ZeroRtt,
+            events::KeyType::OneRtt { generation, .. } => {
+                QuicKeyType::OneRtt { generation }
+            }
+            _ => return,
+        };
+
+        context.push(QuicEventKind::KeyUpdated(key_type));
+    }
+}
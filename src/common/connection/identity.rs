@@ -0,0 +1,85 @@
+use bevy::ecs::component::Component;
+use bytes::Bytes;
+use s2n_quic::provider::event::{
+    ConnectionInfo, ConnectionMeta, Subscriber, events::TlsExporterReady,
+};
+
+/// The certificate chain the peer presented during the TLS handshake.
+///
+/// This component is added next to a [QuicConnection][crate::common::connection::QuicConnection]
+/// whenever the peer presented a certificate that was verified against the endpoint's trust store.
+/// On a client this is the server's chain, on a server this is only present for clients which
+/// connected with a client certificate, see
+/// [with_client_authentication][crate::server::config::QuicServerConfig::with_client_authentication()].
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct QuicPeerIdentity {
+    certificate_chain: Vec<Bytes>,
+}
+
+impl QuicPeerIdentity {
+    pub fn new(certificate_chain: Vec<Bytes>) -> Self {
+        Self { certificate_chain }
+    }
+
+    /// The DER encoded certificates of the peer, starting with the peer's own
+    /// certificate followed by any intermediates up to the trusted root.
+    pub fn certificate_chain(&self) -> &[Bytes] {
+        &self.certificate_chain
+    }
+
+    /// The DER encoded certificate belonging to the peer itself.
+    pub fn leaf_certificate(&self) -> Option<&Bytes> {
+        self.certificate_chain.first()
+    }
+}
+
+/// Event subscriber which captures the peer certificate chain of every
+/// connection once the handshake has completed.
+#[derive(Debug, Default)]
+pub(crate) struct PeerIdentitySubscriber;
+
+/// The per connection storage of [PeerIdentitySubscriber], queried when the
+/// connection is handed over to a [QuicConnection][crate::common::connection::QuicConnection].
+#[derive(Debug, Default)]
+pub(crate) struct PeerIdentityContext {
+    identity: Option<QuicPeerIdentity>,
+}
+
+impl PeerIdentityContext {
+    pub(crate) fn identity(&self) -> Option<QuicPeerIdentity> {
+        self.identity.clone()
+    }
+}
+
+impl Subscriber for PeerIdentitySubscriber {
+    type ConnectionContext = PeerIdentityContext;
+
+    fn create_connection_context(
+        &mut self,
+        _meta: &ConnectionMeta,
+        _info: &ConnectionInfo,
+    ) -> Self::ConnectionContext {
+        PeerIdentityContext::default()
+    }
+
+    fn on_tls_exporter_ready(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &TlsExporterReady,
+    ) {
+        // An error here means the peer never sent a certificate, which is
+        // expected for clients connecting to a server without client auth.
+        let Ok(chain) = event.session.peer_cert_chain_der() else {
+            return;
+        };
+
+        if chain.is_empty() {
+            return;
+        }
+
+        context.identity = Some(QuicPeerIdentity::new(
+            chain.into_iter().map(Bytes::from).collect(),
+        ));
+    }
+}
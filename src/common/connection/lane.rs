@@ -0,0 +1,209 @@
+use bevy::ecs::component::Component;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::common::stream::framing::{decode_header, encode_header};
+
+/// The tag of session packets which weren't sent on a lane.
+const SESSION_TAG: u64 = 0;
+/// The low bit of a lane tag, set if a sequence number follows the tag.
+const SEQUENCED_BIT: u64 = 1;
+
+/// How many bytes the lane tag adds to a session packet which isn't on a lane.
+pub(crate) const SESSION_HEADER_LEN: usize = 1;
+
+/// How the messages of a lane are delivered, mirroring `aeronet_transport::lane::LaneKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuicLaneKind {
+    UnreliableUnordered,
+    UnreliableSequenced,
+    ReliableUnordered,
+    ReliableOrdered,
+}
+
+impl QuicLaneKind {
+    pub fn is_reliable(&self) -> bool {
+        matches!(self, Self::ReliableUnordered | Self::ReliableOrdered)
+    }
+
+    /// Returns `true` if older messages of the lane are dropped once a newer one has arrived.
+    pub fn is_sequenced(&self) -> bool {
+        matches!(self, Self::UnreliableSequenced)
+    }
+}
+
+/// Maps the lanes of a [QuicConnectionSession][crate::common::connection::session::QuicConnectionSession]
+/// to QUIC, used by [send_on_lane][crate::common::connection::session::QuicConnectionSession::send_on_lane()].
+///
+/// Each reliable ordered lane gets its own long-lived stream and each message of a reliable
+/// unordered lane its own short-lived stream, so a lost packet never holds up another lane.
+/// Unreliable lanes are sent as datagrams, or on short-lived streams if the endpoint doesn't
+/// have datagrams enabled. Unreliable sequenced messages carry a sequence number, the peer
+/// drops any which arrive after a newer one.
+///
+/// Lanes are identified by their index, which is sent along with every message. List the lanes
+/// in the same order as the lanes of the `aeronet_transport` transport, so a lane has the same
+/// index in both. Both peers should use the same config, messages received on a lane this config
+/// doesn't have are dropped.
+///
+/// ```ignore
+/// const STATE: usize = 0;
+/// const CHAT: usize = 1;
+///
+/// commands.entity(connection_entity).insert((
+///     QuicConnectionSession::default(),
+///     QuicLaneConfig::new([QuicLaneKind::UnreliableSequenced, QuicLaneKind::ReliableOrdered]),
+/// ));
+/// ```
+#[derive(Debug, Clone, Default, Component)]
+pub struct QuicLaneConfig {
+    lanes: Vec<QuicLaneKind>,
+}
+
+impl QuicLaneConfig {
+    pub fn new(lanes: impl IntoIterator<Item = QuicLaneKind>) -> Self {
+        Self {
+            lanes: lanes.into_iter().collect(),
+        }
+    }
+
+    /// Adds a lane, its index is the number of lanes added before it.
+    pub fn with_lane(mut self, kind: QuicLaneKind) -> Self {
+        self.lanes.push(kind);
+        self
+    }
+
+    pub fn lane(&self, index: usize) -> Option<QuicLaneKind> {
+        self.lanes.get(index).copied()
+    }
+
+    pub fn lanes(&self) -> &[QuicLaneKind] {
+        &self.lanes
+    }
+}
+
+/// A session packet as it's sent to the peer, tagged with the lane it was sent on.
+///
+/// The tag is a QUIC variable length integer, `0` for session packets which aren't on a
+/// lane and `(lane + 1) << 1` otherwise, with the low bit set if a big endian `u16`
+/// sequence number follows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LaneMessage {
+    pub lane: Option<usize>,
+    pub sequence: Option<u16>,
+    pub payload: Bytes,
+}
+
+impl LaneMessage {
+    pub fn encode(&self) -> Bytes {
+        let tag = match self.lane {
+            None => SESSION_TAG,
+            Some(lane) => {
+                (lane as u64 + 1) << 1 | self.sequence.map_or(0, |_| SEQUENCED_BIT)
+            }
+        };
+
+        let header = encode_header(tag as usize);
+        let mut message = BytesMut::with_capacity(header.len() + 2 + self.payload.len());
+        message.put(header);
+
+        if let Some(sequence) = self.sequence {
+            message.put_u16(sequence);
+        }
+
+        message.put_slice(&self.payload);
+        message.freeze()
+    }
+
+    /// Returns `None` if the packet is too short for its tag or the tag isn't valid.
+    pub fn decode(mut packet: Bytes) -> Option<Self> {
+        let (tag, tag_size) = decode_header(&packet)?;
+        packet.advance(tag_size);
+
+        if tag == SESSION_TAG {
+            return Some(Self {
+                lane: None,
+                sequence: None,
+                payload: packet,
+            });
+        }
+
+        // The peer picks the tag, a sequence bit without a lane isn't valid
+        let lane = usize::try_from((tag >> 1).checked_sub(1)?).ok()?;
+
+        let sequence = if tag & SEQUENCED_BIT != 0 {
+            if packet.len() < 2 {
+                return None;
+            }
+
+            Some(packet.get_u16())
+        } else {
+            None
+        };
+
+        Some(Self {
+            lane: Some(lane),
+            sequence,
+            payload: packet,
+        })
+    }
+}
+
+/// Returns `true` if `sequence` is newer than `last`, allowing for wrap around.
+pub(crate) fn is_newer(sequence: u16, last: u16) -> bool {
+    (sequence.wrapping_sub(last) as i16) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(lane: Option<usize>, sequence: Option<u16>) {
+        let message = LaneMessage {
+            lane,
+            sequence,
+            payload: Bytes::from_static(b"payload"),
+        };
+
+        assert_eq!(LaneMessage::decode(message.encode()), Some(message));
+    }
+
+    #[test]
+    fn round_trips_lane_messages() {
+        round_trip(None, None);
+        round_trip(Some(0), None);
+        round_trip(Some(0), Some(7));
+        round_trip(Some(30), Some(u16::MAX));
+        round_trip(Some(5000), None);
+    }
+
+    #[test]
+    fn session_packets_cost_one_byte() {
+        let message = LaneMessage {
+            lane: None,
+            sequence: None,
+            payload: Bytes::from_static(b"payload"),
+        };
+
+        assert_eq!(message.encode().len(), SESSION_HEADER_LEN + 7);
+    }
+
+    #[test]
+    fn refuses_truncated_messages() {
+        assert_eq!(LaneMessage::decode(Bytes::new()), None);
+
+        // The tag of lane 0 with a sequence number, but only one byte of it
+        assert_eq!(LaneMessage::decode(Bytes::from_static(&[0b11, 0])), None);
+
+        // A sequence number with no lane
+        assert_eq!(LaneMessage::decode(Bytes::from_static(&[0b01, 0, 0])), None);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        assert!(is_newer(1, 0));
+        assert!(is_newer(0, u16::MAX));
+        assert!(!is_newer(0, 0));
+        assert!(!is_newer(u16::MAX, 0));
+        assert!(!is_newer(0, 1));
+    }
+}
@@ -1,3 +1,4 @@
+use aeronet_io::{SessionEndpoint, packet::RecvPacket};
 use bevy::{
     ecs::component::Component,
     log::{
@@ -7,8 +8,18 @@ use bevy::{
     },
     prelude::{Deref, DerefMut},
 };
-use s2n_quic::{Connection, application, connection::Handle as ConnectionHandle};
-use std::sync::Arc;
+use bytes::Bytes;
+use s2n_quic::{
+    Connection,
+    connection::Handle as ConnectionHandle,
+    provider::datagram::default::{
+        Receiver as DatagramReceiver, Sender as DatagramSender,
+    },
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     runtime::Handle,
     sync::{
@@ -22,25 +33,41 @@ use crate::common::{
     QuicParentId,
     attempt::{QuicActionAttempt, TaskError},
     connection::{
+        control::open_control_stream,
+        datagram::{DATAGRAM_FRAME_OVERHEAD, MIN_DATAGRAM_MTU, QuicDatagramError},
         disconnect::ConnectionDisconnectReason,
+        event::{EventBridgeContext, QuicEventKind},
         id::ConnectionId,
+        identity::{PeerIdentityContext, QuicPeerIdentity},
         open_flag::OpenFlag,
+        state::QuicConnectionState,
+        stats::{QuicConnectionStats, StatsContext},
         stream_flag::StreamFlag,
         task::{
             ConnectionCommand, ConnectionCommandError, ConnectionHandleTask,
-            ConnectionTask, ConnectionTaskState,
+            ConnectionTask, ConnectionTaskState, PeerStreamFilter,
         },
     },
+    error_code::QuicErrorCode,
+    orchestrator::AsyncOrchestrator,
     stream::{
         QuicBidirectionalStreamAttempt, QuicPeerStreamAttempt, QuicReceiveStreamAttempt,
-        QuicSendStreamAttempt,
+        QuicSendStreamAttempt, acceptor::AcceptPolicy,
     },
 };
 
+pub(super) mod control;
+pub mod datagram;
 pub mod disconnect;
+pub mod event;
 pub mod id;
+pub mod identity;
+pub mod lane;
 pub(super) mod open_flag;
 pub mod plugin;
+pub mod session;
+pub mod state;
+pub mod stats;
 pub(super) mod stream_flag;
 pub mod task;
 
@@ -62,6 +89,7 @@ type ConnectionResponse<T> = Result<Option<T>, TaskError>;
 /// will be added on the entity.
 #[derive(Deref, DerefMut, Component)]
 #[component(storage = "SparseSet")]
+#[require(QuicConnectionState, SessionEndpoint)]
 pub struct QuicConnectionAttempt(QuicActionAttempt<Connection>);
 
 impl QuicConnectionAttempt {
@@ -72,13 +100,27 @@ impl QuicConnectionAttempt {
     ) -> Self {
         Self(QuicActionAttempt::new(handle, conn_task, parent_id))
     }
+
+    /// Fails the attempt with [QuicActionError::TimedOut][crate::common::attempt::QuicActionError::TimedOut]
+    /// if no connection has been made within the given duration.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
 }
 
 /// The component analogue to [Connection] in s2n-quic.
 /// This component manages the async behaviour of our Quic connection.
 #[derive(Debug, Component)]
+#[require(
+    QuicConnectionStats,
+    QuicConnectionState::Handshaking,
+    AcceptPolicy,
+    SessionEndpoint
+)]
 pub struct QuicConnection {
-    runtime: Handle,
+    /// Runs the connection task and the tasks of all its streams
+    orchestrator: AsyncOrchestrator,
     conn_handle: ConnectionHandle,
     task_state: ConnectionTaskState,
     conn_command_channel: mpsc::Sender<ConnectionCommand>,
@@ -86,6 +128,12 @@ pub struct QuicConnection {
     connection_id: ConnectionId,
     /// Flag set by async wakers as soon as there's a new stream
     pending_stream: Arc<StreamFlag>,
+    /// Whether the endpoint for this connection was built with datagram support
+    datagrams_enabled: bool,
+    /// The verified certificate chain of the peer, if one was presented
+    peer_identity: Option<QuicPeerIdentity>,
+    /// Keeps the connection counted by the admission policy of its server
+    admission_ticket: Option<Arc<()>>,
 }
 
 impl QuicConnection {
@@ -111,29 +159,51 @@ impl QuicConnection {
             );
         }
 
+        let datagrams_enabled = connection
+            .datagram_mut(|_: &mut DatagramReceiver| ())
+            .is_ok();
+
+        let peer_identity = connection
+            .query_event_context(|ctx: &PeerIdentityContext| ctx.identity())
+            .ok()
+            .flatten();
+
+        // Opened before the connection is handed out, so it's the first of our streams
+        let control_stream = open_control_stream(&mut connection);
+
         let is_open = OpenFlag::new(true);
         let conn_handle = connection.handle();
+        let orchestrator = AsyncOrchestrator::new(runtime);
         let task = ConnectionTask::new(
             connection,
             rec,
             connection_id,
             is_open.clone(),
             pending_stream.clone(),
+            control_stream,
+            orchestrator.clone(),
         );
 
-        let handle = runtime.spawn(task.start());
+        let task_state: ConnectionTaskState = orchestrator.spawn(task.start());
 
         Self {
-            runtime: runtime.clone(),
+            orchestrator,
             conn_handle,
-            task_state: ConnectionTaskState::new(runtime, handle),
+            task_state,
             conn_command_channel: send,
             is_open,
             connection_id,
             pending_stream,
+            datagrams_enabled,
+            peer_identity,
+            admission_ticket: None,
         }
     }
 
+    pub(crate) fn set_admission_ticket(&mut self, ticket: Arc<()>) {
+        self.admission_ticket = Some(ticket);
+    }
+
     /// Accepts any incoming streams, this will always return an [QuicPeerStreamAttempt] even if
     /// there are no pending streams.
     ///
@@ -143,20 +213,38 @@ impl QuicConnection {
     /// Returns an error if the async communication channel errors out due to being full.
     pub fn accept_stream(
         &mut self,
+    ) -> Result<QuicPeerStreamAttempt, ConnectionCommandError> {
+        self.accept_filtered_stream(PeerStreamFilter {
+            bidirectional: true,
+            unidirectional: true,
+            refuse_code: QuicErrorCode::NO_ERROR.into(),
+        })
+    }
+
+    /// Accepts any incoming stream the filter allows, refusing the stream otherwise.
+    pub(crate) fn accept_filtered_stream(
+        &mut self,
+        filter: PeerStreamFilter,
     ) -> Result<QuicPeerStreamAttempt, ConnectionCommandError> {
         self.pending_stream.set_false();
 
         let (send, rec) = oneshot::channel();
 
-        let cmd = ConnectionCommand::Accept { respond_to: send };
+        let cmd = ConnectionCommand::Accept {
+            respond_to: send,
+            filter,
+        };
         let send_res = self.conn_command_channel.try_send(cmd);
 
         if let Err(err) = send_res {
             return Err(err.into());
         }
 
-        let attempt =
-            QuicPeerStreamAttempt::new(self.runtime.clone(), rec, self.parent_id());
+        let attempt = QuicPeerStreamAttempt::new(
+            self.orchestrator.runtime().clone(),
+            rec,
+            self.parent_id(),
+        );
 
         Ok(attempt)
     }
@@ -182,8 +270,11 @@ impl QuicConnection {
             return Err(err.into());
         }
 
-        let attempt =
-            QuicReceiveStreamAttempt::new(self.runtime.clone(), rec, self.parent_id());
+        let attempt = QuicReceiveStreamAttempt::new(
+            self.orchestrator.runtime().clone(),
+            rec,
+            self.parent_id(),
+        );
 
         Ok(attempt)
     }
@@ -210,7 +301,7 @@ impl QuicConnection {
         }
 
         let attempt = QuicBidirectionalStreamAttempt::new(
-            self.runtime.clone(),
+            self.orchestrator.runtime().clone(),
             rec,
             self.parent_id(),
         );
@@ -226,12 +317,13 @@ impl QuicConnection {
             self.conn_handle.clone(),
             self.is_open.clone(),
             self.connection_id,
+            self.orchestrator.clone(),
         );
 
-        let join = self.runtime.spawn(task.open_bidirectional());
+        let join = self.orchestrator.runtime().spawn(task.open_bidirectional());
 
         Ok(QuicBidirectionalStreamAttempt::new(
-            self.runtime.clone(),
+            self.orchestrator.runtime().clone(),
             join,
             self.parent_id(),
         ))
@@ -245,26 +337,153 @@ impl QuicConnection {
             self.conn_handle.clone(),
             self.is_open.clone(),
             self.connection_id,
+            self.orchestrator.clone(),
         );
 
-        let join = self.runtime.spawn(task.open_send());
+        let join = self.orchestrator.runtime().spawn(task.open_send());
 
         Ok(QuicSendStreamAttempt::new(
-            self.runtime.clone(),
+            self.orchestrator.runtime().clone(),
             join,
             self.parent_id(),
         ))
     }
 
-    #[tracing::instrument(skip(self), fields(connection_id = %self.connection_id, remote_addr = ?self.conn_handle.remote_addr()))]
-    pub fn close(&self, code: application::Error) {
+    /// Queues an unreliable, unordered datagram to be sent to the peer.
+    ///
+    /// Datagrams are never retransmitted and do not head-of-line block any
+    /// streams on this connection. A datagram larger than the current path
+    /// allows will be silently dropped by the connection, see
+    /// [max_datagram_size][Self::max_datagram_size()].
+    pub fn send_datagram(&self, data: Bytes) -> Result<(), QuicDatagramError> {
+        if !self.datagrams_enabled {
+            return Err(QuicDatagramError::Disabled);
+        }
+
+        self.conn_handle
+            .datagram_mut(|sender: &mut DatagramSender| sender.send_datagram(data))
+            .map_err(|_| QuicDatagramError::LockPoisoned)?
+            .map_err(QuicDatagramError::from)
+    }
+
+    /// Receives a single datagram from the peer.
+    ///
+    /// Received datagrams are held in a bounded queue, see
+    /// [QuicDatagramConfig][crate::common::connection::datagram::QuicDatagramConfig].
+    /// If the queue isn't drained fast enough the oldest datagrams are dropped.
+    pub fn recv_datagram(&mut self) -> Option<RecvPacket> {
+        if !self.datagrams_enabled {
+            return None;
+        }
+
+        let payload = self
+            .conn_handle
+            .datagram_mut(|receiver: &mut DatagramReceiver| receiver.recv_datagram())
+            .ok()
+            .flatten()?;
+
+        Some(RecvPacket {
+            recv_at: Instant::now(),
+            payload,
+        })
+    }
+
+    /// Receives up to `limit` datagrams and pushes them to the given buffer.
+    /// Returns the number of datagrams received.
+    pub fn recv_datagrams(
+        &mut self,
+        buffer: &mut Vec<RecvPacket>,
+        limit: usize,
+    ) -> usize {
+        if !self.datagrams_enabled {
+            return 0;
+        }
+
+        let recv_at = Instant::now();
+
+        self.conn_handle
+            .datagram_mut(|receiver: &mut DatagramReceiver| {
+                let mut count = 0;
+
+                while count < limit {
+                    let Some(payload) = receiver.recv_datagram() else {
+                        break;
+                    };
+
+                    buffer.push(RecvPacket { recv_at, payload });
+                    count += 1;
+                }
+
+                count
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if datagrams can be sent and received over this connection.
+    pub fn datagrams_enabled(&self) -> bool {
+        self.datagrams_enabled
+    }
+
+    /// The largest datagram payload which currently fits in a packet on this connection,
+    /// going by the space recent packets had left for datagrams, so it shrinks along with
+    /// the path. Never less than [MIN_DATAGRAM_MTU], which fits in a packet of its own on
+    /// any path. Returns `None` if datagrams are disabled.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        if !self.datagrams_enabled {
+            return None;
+        }
+
+        let packet_space = self
+            .conn_handle
+            .datagram_mut(|sender: &mut DatagramSender| sender.smoothed_packet_space())
+            .ok()?;
+
+        Some(
+            packet_space
+                .saturating_sub(DATAGRAM_FRAME_OVERHEAD)
+                .max(MIN_DATAGRAM_MTU),
+        )
+    }
+
+    /// Closes the connection with the given code. The connection is marked as closed
+    /// straight away, moving its entity to [QuicConnectionState::Closing].
+    pub fn close(&self, code: QuicErrorCode) {
+        self.send_close(code, ConnectionCommand::Close(code.into()));
+    }
+
+    /// Like [close][Self::close()], but first sends the reason to the peer, whose
+    /// connection then disconnects with [ConnectionDisconnectReason::PeerDisconnected].
+    ///
+    /// QUIC can't attach a reason to an application close, so it's sent on the control
+    /// stream, the first unidirectional stream each side opens. The close waits up to
+    /// 250ms for the peer to receive the reason, without holding up the connection. Reasons
+    /// longer than [MAX_CLOSE_REASON_LEN][disconnect::MAX_CLOSE_REASON_LEN] are truncated.
+    pub fn close_with_reason(&self, code: QuicErrorCode, reason: impl Into<String>) {
+        let mut reason = reason.into();
+
+        if reason.len() > disconnect::MAX_CLOSE_REASON_LEN {
+            let mut end = disconnect::MAX_CLOSE_REASON_LEN;
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            reason.truncate(end);
+        }
+
+        self.send_close(
+            code,
+            ConnectionCommand::CloseWithReason(code.into(), reason),
+        );
+    }
+
+    #[tracing::instrument(skip(self, command), fields(connection_id = %self.connection_id, remote_addr = ?self.conn_handle.remote_addr()))]
+    fn send_close(&self, code: QuicErrorCode, command: ConnectionCommand) {
         if !self.is_open() {
             return;
         }
 
-        let res = self
-            .conn_command_channel
-            .try_send(ConnectionCommand::Close(code));
+        self.is_open.set_closed();
+
+        let res = self.conn_command_channel.try_send(command);
 
         let Err(err) = res else {
             return;
@@ -281,7 +500,7 @@ impl QuicConnection {
             ),
         }
 
-        self.conn_handle.close(code);
+        self.conn_handle.close(code.into());
     }
 
     /// Returns true if the connection is still open.
@@ -289,6 +508,15 @@ impl QuicConnection {
         !self.task_state.is_finished() && self.is_open.get()
     }
 
+    /// Returns `true` once the peer has confirmed the handshake. Also returns `true` if
+    /// the handshake state can't be read, e.g. when the connection wasn't created by a
+    /// [QuicServer][crate::server::QuicServer] or [QuicClient][crate::client::QuicClient].
+    pub fn is_handshake_confirmed(&self) -> bool {
+        self.conn_handle
+            .query_event_context(|ctx: &EventBridgeContext| ctx.handshake_confirmed())
+            .unwrap_or(true)
+    }
+
     /// Returns true if calling [accept_stream][Self::accept_stream()] will return something different.
     /// This doesn't necessarily mean there's a pending connection, just that
     /// calling accept() will return something different.
@@ -313,6 +541,39 @@ impl QuicConnection {
         self.task_state.get_disconnect_reason()
     }
 
+    /// The certificate chain the peer presented during the handshake. Returns `None` if the
+    /// peer didn't present a certificate.
+    ///
+    /// The default plugins also add this as a [QuicPeerIdentity] component on the connection entity.
+    pub fn peer_identity(&self) -> Option<&QuicPeerIdentity> {
+        self.peer_identity.as_ref()
+    }
+
+    /// Reads the latest [QuicConnectionStats] from the connection. Returns `None` if the
+    /// connection wasn't created by a [QuicServer][crate::server::QuicServer] or
+    /// [QuicClient][crate::client::QuicClient] or has been closed.
+    pub fn stats(&self) -> Option<QuicConnectionStats> {
+        self.conn_handle
+            .query_event_context(|ctx: &StatsContext| ctx.stats())
+            .ok()
+    }
+
+    /// Moves the s2n-quic events queued for this connection into the buffer,
+    /// returning how many events were dropped because the queue was full.
+    pub(crate) fn drain_events(
+        &mut self,
+        buffer: &mut Vec<(Instant, QuicEventKind)>,
+    ) -> usize {
+        self.conn_handle
+            .query_event_context_mut(|ctx: &mut EventBridgeContext| ctx.drain(buffer))
+            .unwrap_or(0)
+    }
+
+    /// The orchestrator running this connection and all of its streams.
+    pub fn orchestrator(&self) -> &AsyncOrchestrator {
+        &self.orchestrator
+    }
+
     /// Gets the ID information for the parent client or server for this connection
     pub fn parent_id(&self) -> QuicParentId {
         self.connection_id.parent_id()
@@ -1,16 +1,24 @@
 use bevy::{
     app::{Plugin, Update},
     ecs::{
+        change_detection::DetectChangesMut,
         entity::Entity,
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query, Res},
     },
     log::{error, info, tracing},
 };
 
-use crate::common::{
-    attempt::QuicActionError,
-    connection::{QuicConnection, QuicConnectionAttempt},
-    runtime::TokioRuntime,
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::{
+        attempt::QuicActionError,
+        connection::{
+            QuicConnection, QuicConnectionAttempt,
+            state::{QuicConnected, QuicConnectionFailed, QuicConnectionState},
+        },
+        runtime::TokioRuntime,
+    },
 };
 
 #[derive(Debug)]
@@ -18,7 +26,12 @@ pub struct ConnectionAttemptPlugin;
 
 impl Plugin for ConnectionAttemptPlugin {
     fn build(&self, app: &mut bevy::app::App) {
-        app.add_systems(Update, handle_connection_attempts);
+        app.add_systems(
+            Update,
+            (handle_connection_attempts, handle_handshakes)
+                .chain()
+                .run_if(quic_tick_ready),
+        );
     }
 }
 
@@ -26,12 +39,12 @@ impl Plugin for ConnectionAttemptPlugin {
 fn handle_connection_attempts(
     mut commands: Commands,
     runtime: Res<TokioRuntime>,
-    query: Query<(Entity, &mut QuicConnectionAttempt)>,
+    query: Query<(Entity, &mut QuicConnectionAttempt, &mut QuicConnectionState)>,
 ) {
     let handle_ref = runtime.handle();
 
     for entity_bundle in query {
-        let (entity, mut attempt) = entity_bundle;
+        let (entity, mut attempt, mut state) = entity_bundle;
         let parent_id = attempt.parent_id();
 
         let res = attempt.attempt_result();
@@ -39,8 +52,10 @@ fn handle_connection_attempts(
         if let Err(e) = res {
             match e {
                 QuicActionError::Pending => {
+                    // A new attempt may have been put on a previously closed connection entity
+                    state.set_if_neq(QuicConnectionState::Connecting);
                     continue;
-                } // TODO: Setup a timeout
+                }
                 QuicActionError::Consumed => {
                     info!(
                         "Already consumed connection attempt hasn't been cleaned up: {entity}"
@@ -52,8 +67,23 @@ fn handle_connection_attempts(
                 QuicActionError::Crashed(ref join_error) => {
                     error!("Error joining connection attempt: {:?}", join_error)
                 }
+                QuicActionError::StreamFailed(error) => {
+                    error!("Error handling connection attempt: {:?}", error)
+                }
+                QuicActionError::TimedOut(timeout) => {
+                    error!("Connection attempt timed out after {:?}", timeout)
+                }
+                QuicActionError::Cancelled => {
+                    info!("Connection attempt cancelled: {entity}")
+                }
             }
 
+            *state = QuicConnectionState::Closed;
+            commands.trigger(QuicConnectionFailed {
+                entity,
+                error: e.clone(),
+            });
+
             let mut error_entity = commands.entity(entity);
 
             #[cfg(feature = "connection-errors")]
@@ -63,10 +93,7 @@ fn handle_connection_attempts(
                     std::time::SystemTime,
                 };
 
-                let err_comp = QuicActionErrorComponent::new(e, SystemTime::now());
-                let err_bundle = (err_comp, *parent_id);
-
-                error_entity.insert(err_bundle);
+                error_entity.insert(QuicActionErrorComponent::new(e, SystemTime::now()));
             }
 
             error_entity.remove::<QuicConnectionAttempt>();
@@ -77,10 +104,41 @@ fn handle_connection_attempts(
         info!("New connection entity with {parent_id}");
         let conn = res.unwrap();
         let quic_conn = QuicConnection::new(handle_ref.clone(), conn, parent_id);
+        let peer_identity = quic_conn.peer_identity().cloned();
+        *state = QuicConnectionState::Handshaking;
 
-        commands
-            .entity(entity)
+        let mut conn_entity = commands.entity(entity);
+        conn_entity
             .remove::<QuicConnectionAttempt>()
             .insert(quic_conn);
+
+        if let Some(identity) = peer_identity {
+            conn_entity.insert(identity);
+        }
+    }
+}
+
+/// Moves connections to [QuicConnectionState::Connected] once the peer has confirmed the handshake.
+fn handle_handshakes(
+    mut commands: Commands,
+    query: Query<(Entity, &QuicConnection, &mut QuicConnectionState)>,
+) {
+    for (entity, connection, mut state) in query {
+        if !matches!(
+            *state,
+            QuicConnectionState::Connecting | QuicConnectionState::Handshaking
+        ) {
+            continue;
+        }
+
+        if !connection.is_open() || !connection.is_handshake_confirmed() {
+            continue;
+        }
+
+        *state = QuicConnectionState::Connected;
+        commands.trigger(QuicConnected {
+            entity,
+            connection_id: connection.id(),
+        });
     }
 }
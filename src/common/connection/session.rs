@@ -0,0 +1,636 @@
+use aeronet_io::{Session, packet::RecvPacket};
+use bevy::{
+    ecs::{component::Component, system::Query},
+    log::{tracing, warn},
+};
+use bytes::Bytes;
+use std::{collections::HashMap, fmt, iter, mem, time::Instant};
+
+use crate::common::{
+    attempt::QuicActionError,
+    connection::{
+        QuicConnection,
+        datagram::MIN_DATAGRAM_MTU,
+        lane::{LaneMessage, QuicLaneConfig, QuicLaneKind, SESSION_HEADER_LEN, is_newer},
+        stats::QuicConnectionStats,
+    },
+    stream::{
+        QuicPeerStream, QuicPeerStreamAttempt, QuicSendStreamAttempt,
+        framing::SendMessageError, receive::QuicReceiveStream, send::QuicSendStream,
+    },
+};
+
+/// How many packets are moved between the session and the connection per frame at most
+const MAX_PACKET_TRANSFER: usize = 512;
+/// How many received lane messages are held until they're drained before new ones are dropped
+const MAX_LANE_BACKLOG: usize = 4096;
+/// The most a QUIC packet adds around a session packet sent on a stream: a worst case
+/// short header, the AEAD tag, a stream frame header and the message length prefix.
+const STREAM_PACKET_OVERHEAD: usize = 25 + 16 + 25 + 8;
+
+/// How the packets of a [QuicConnectionSession] are sent to the peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuicSessionTransport {
+    /// Every packet is sent in order over a single long-lived stream.
+    #[default]
+    OrderedStream,
+    /// Every packet is sent on its own short-lived stream, so a lost packet only holds up itself.
+    UnorderedStreams,
+    /// Every packet is sent as a datagram, which may be lost or arrive out of order.
+    /// Needs datagrams to be enabled on the endpoint.
+    Datagrams,
+}
+
+/// Add this component to a [QuicConnection] entity, once it's connected, to use the whole
+/// connection as a single aeronet [Session](https://docs.rs/aeronet_io/latest/aeronet_io/struct.Session.html).
+///
+/// Outgoing packets are sent with the [QuicSessionTransport] of the session, packets the peer
+/// sent on any stream or as a datagram all end up in the session. Streams of the connection are
+/// run by the session, none of them are spawned as entities. The MTU of the session follows the
+/// path MTU of the connection.
+///
+/// Messages can also be sent on a lane with [send_on_lane][Self::send_on_lane()], see [QuicLaneConfig].
+/// The peer receives them from [drain_lane_messages][Self::drain_lane_messages()] rather than the
+/// session. Every packet carries a tag with its lane, so both peers must use this session.
+///
+/// Shouldn't be combined with [QuicDatagramSession][crate::common::stream::session::QuicDatagramSession].
+///
+/// ```ignore
+/// app.add_observer(|event: On<QuicConnected>, mut commands: Commands| {
+///     commands
+///         .entity(event.entity)
+///         .insert(QuicConnectionSession::new(QuicSessionTransport::Datagrams));
+/// });
+/// ```
+#[derive(Default, Component)]
+#[require(Session::new(Instant::now(), MIN_DATAGRAM_MTU))]
+pub struct QuicConnectionSession {
+    transport: QuicSessionTransport,
+    ordered: OrderedStream,
+    /// The long-lived stream of each reliable ordered lane
+    ordered_lanes: HashMap<usize, OrderedStream>,
+    /// Messages waiting to be sent on their lane
+    lane_send: Vec<(usize, Bytes)>,
+    /// The sequence number of the next message of each sequenced lane
+    send_sequences: HashMap<usize, u16>,
+    /// The newest sequence number received on each sequenced lane
+    recv_sequences: HashMap<usize, u16>,
+    /// Messages received on a lane which haven't been drained yet
+    lane_recv: Vec<(usize, RecvPacket)>,
+    /// Packets received this frame which haven't been routed yet
+    received: Vec<RecvPacket>,
+    /// Streams being opened for a single packet each
+    unordered: Vec<(QuicSendStreamAttempt, Bytes)>,
+    /// Packets which didn't make it onto their own stream, sent again next frame
+    unordered_retry: Vec<Bytes>,
+    /// Send streams which are finishing, kept until everything has been sent
+    finishing: Vec<QuicSendStream>,
+    accepting: Vec<QuicPeerStreamAttempt>,
+    receiving: Vec<QuicReceiveStream>,
+    /// Set once the send streams should finish, see [finish_streams][Self::finish_streams()]
+    finish_requested: bool,
+}
+
+/// A long-lived stream which sends packets in order, along with the packets which
+/// haven't made it onto the stream yet.
+#[derive(Default)]
+struct OrderedStream {
+    state: OrderedStreamState,
+    /// Packets waiting for the stream to open or for room in its outbound channel
+    queued: Vec<Bytes>,
+}
+
+#[derive(Default)]
+enum OrderedStreamState {
+    #[default]
+    Closed,
+    Opening(QuicSendStreamAttempt),
+    Open(QuicSendStream),
+}
+
+impl OrderedStream {
+    /// Queues the packets behind any which haven't been sent yet.
+    fn queue(&mut self, packets: impl Iterator<Item = Bytes>) {
+        self.queued.extend(packets);
+    }
+
+    fn has_stream(&self) -> bool {
+        !matches!(self.state, OrderedStreamState::Closed)
+    }
+
+    /// Finishes the stream once every queued packet is on it, handing the stream over so
+    /// it can be kept until the peer has everything.
+    fn finish(&mut self) -> Option<QuicSendStream> {
+        if !self.queued.is_empty() {
+            return None;
+        }
+
+        match mem::take(&mut self.state) {
+            OrderedStreamState::Open(mut send) => {
+                let _ = send.close();
+                Some(send)
+            }
+            state => {
+                self.state = state;
+                None
+            }
+        }
+    }
+
+    /// Sends as many queued packets as the stream has room for, opening the stream
+    /// first if it isn't open yet. Whatever doesn't fit is sent on a later poll.
+    fn poll(&mut self, connection: &mut QuicConnection) {
+        if let OrderedStreamState::Opening(attempt) = &mut self.state {
+            match attempt.attempt_result() {
+                Err(QuicActionError::Pending) => return,
+                Ok(Some(send)) => self.state = OrderedStreamState::Open(send),
+                Ok(None) | Err(_) => {
+                    warn!(
+                        "Unable to open session stream on {}, {} packets will be dropped",
+                        connection.id(),
+                        self.queued.len()
+                    );
+                    self.queued.clear();
+                    self.state = OrderedStreamState::Closed;
+                    return;
+                }
+            }
+        }
+
+        if let OrderedStreamState::Open(send) = &self.state
+            && !send.is_open()
+        {
+            // Opened again below if there's anything left to send
+            self.state = OrderedStreamState::Closed;
+        }
+
+        if self.queued.is_empty() {
+            return;
+        }
+
+        match &mut self.state {
+            OrderedStreamState::Closed => match connection.open_send_stream() {
+                Ok(attempt) => self.state = OrderedStreamState::Opening(attempt),
+                Err(e) => warn!(
+                    "Unable to open session stream on {}, retrying next frame: {}",
+                    connection.id(),
+                    e
+                ),
+            },
+            OrderedStreamState::Opening(_) => {}
+            OrderedStreamState::Open(send) => {
+                while !self.queued.is_empty() {
+                    match send.send_messages_drain(&mut self.queued) {
+                        Ok(()) => {}
+                        // The message was too large and has been dropped
+                        Err(SendMessageError::Framing(e)) => warn!(
+                            "Dropping packet on session stream {}: {}",
+                            send.id(),
+                            e
+                        ),
+                        // Kept for the next poll
+                        Err(SendMessageError::Full(_)) => break,
+                        Err(SendMessageError::Closed(_)) => {
+                            self.state = OrderedStreamState::Closed;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for QuicConnectionSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicConnectionSession")
+            .field("transport", &self.transport)
+            .field("streams", &self.stream_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl QuicConnectionSession {
+    pub fn new(transport: QuicSessionTransport) -> Self {
+        Self {
+            transport,
+            ..Default::default()
+        }
+    }
+
+    pub fn transport(&self) -> QuicSessionTransport {
+        self.transport
+    }
+
+    /// Changes how packets are sent from now on, packets already sent are unaffected.
+    pub fn set_transport(&mut self, transport: QuicSessionTransport) {
+        self.transport = transport;
+    }
+
+    /// How many streams the session is currently sending or receiving on.
+    pub fn stream_count(&self) -> usize {
+        let ordered = self
+            .ordered_lanes
+            .values()
+            .chain(iter::once(&self.ordered))
+            .filter(|stream| stream.has_stream())
+            .count();
+
+        ordered + self.unordered.len() + self.finishing.len() + self.receiving.len()
+    }
+
+    /// Finishes every send stream of the session once everything queued on it has been
+    /// sent, used by [ServerShutdownPlugin][crate::server::shutdown::ServerShutdownPlugin]
+    /// to drain the connection. Anything sent afterwards opens a new stream, which is
+    /// finished the same way.
+    pub(crate) fn finish_streams(&mut self) {
+        self.finish_requested = true;
+    }
+
+    /// Returns `true` while anything the session sent hasn't been acknowledged by the peer.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.ordered_lanes
+            .values()
+            .chain(iter::once(&self.ordered))
+            .any(|stream| stream.has_stream() || !stream.queued.is_empty())
+            || !self.unordered.is_empty()
+            || !self.unordered_retry.is_empty()
+            || !self.finishing.is_empty()
+    }
+
+    /// Queues a message to be sent on the lane with the given index of the connection's
+    /// [QuicLaneConfig] when the session is next flushed.
+    ///
+    /// Messages for a lane the config doesn't have are dropped. The peer receives them from
+    /// [drain_lane_messages][Self::drain_lane_messages()] with the same lane index.
+    pub fn send_on_lane(&mut self, lane: usize, message: Bytes) {
+        self.lane_send.push((lane, message));
+    }
+
+    /// Takes every message the peer sent with [send_on_lane][Self::send_on_lane()] since
+    /// the last call, along with the index of its lane.
+    ///
+    /// Messages of a lane arrive as its [QuicLaneKind] allows. Once more than a few thousand
+    /// are waiting new ones are dropped, so drain them every frame.
+    pub fn drain_lane_messages(
+        &mut self,
+    ) -> impl Iterator<Item = (usize, RecvPacket)> + '_ {
+        self.lane_recv.drain(..)
+    }
+
+    /// Accepts new peer streams and receives packets from every stream and datagram,
+    /// session packets go to the buffer and lane messages are held until they're drained.
+    fn recv(
+        &mut self,
+        connection: &mut QuicConnection,
+        buffer: &mut Vec<RecvPacket>,
+        lanes: Option<&QuicLaneConfig>,
+    ) {
+        if connection.should_poll_accept() {
+            match connection.accept_stream() {
+                Ok(attempt) => self.accepting.push(attempt),
+                Err(e) => warn!(
+                    "Unable to accept session stream on {}: {}",
+                    connection.id(),
+                    e
+                ),
+            }
+        }
+
+        let mut accepting = mem::take(&mut self.accepting);
+
+        accepting.retain_mut(|attempt| match attempt.attempt_result() {
+            Err(QuicActionError::Pending) => true,
+            Ok(Some(QuicPeerStream::Receive(rec))) => {
+                self.receiving.push(rec);
+                false
+            }
+            Ok(Some(QuicPeerStream::Bidirectional(rec, mut send))) => {
+                // The peer's session only sends, there's nothing to answer with
+                let _ = send.close();
+                self.finishing.push(send);
+                self.receiving.push(rec);
+                false
+            }
+            Ok(None) => false,
+            Err(e) => {
+                warn!(
+                    "Unable to accept session stream on {}: {}",
+                    connection.id(),
+                    e
+                );
+                false
+            }
+        });
+
+        self.accepting = accepting;
+
+        let mut received = mem::take(&mut self.received);
+
+        self.receiving.retain_mut(|rec| {
+            // Anything sent before the stream finished may still be waiting to be received,
+            // so a finished stream is only dropped once nothing more comes out of it
+            let open = rec.is_open();
+            let count = rec.recv_messages(&mut received, MAX_PACKET_TRANSFER);
+            open || count > 0
+        });
+
+        if connection.datagrams_enabled() {
+            connection.recv_datagrams(&mut received, MAX_PACKET_TRANSFER);
+        }
+
+        for packet in received.drain(..) {
+            self.route_received(connection, packet, buffer, lanes);
+        }
+
+        // Keeps the allocation around for the next frame
+        self.received = received;
+    }
+
+    /// Sends a received packet to the session or to its lane, by the lane it was sent on.
+    /// Messages on a lane the [QuicLaneConfig] doesn't have are dropped.
+    fn route_received(
+        &mut self,
+        connection: &QuicConnection,
+        packet: RecvPacket,
+        buffer: &mut Vec<RecvPacket>,
+        lanes: Option<&QuicLaneConfig>,
+    ) {
+        let Some(message) = LaneMessage::decode(packet.payload) else {
+            warn!("Dropping malformed session packet from {}", connection.id());
+            return;
+        };
+
+        let packet = RecvPacket {
+            recv_at: packet.recv_at,
+            payload: message.payload,
+        };
+
+        let Some(lane) = message.lane else {
+            buffer.push(packet);
+            return;
+        };
+
+        let Some(kind) = lanes.and_then(|lanes| lanes.lane(lane)) else {
+            warn!(
+                "Message received on unknown lane {} of {}, dropping it",
+                lane,
+                connection.id()
+            );
+            return;
+        };
+
+        if let Some(sequence) = message.sequence.filter(|_| kind.is_sequenced()) {
+            match self.recv_sequences.get(&lane) {
+                // Older than what the lane already received
+                Some(&last) if !is_newer(sequence, last) => return,
+                _ => {
+                    self.recv_sequences.insert(lane, sequence);
+                }
+            }
+        }
+
+        if self.lane_recv.len() >= MAX_LANE_BACKLOG {
+            warn!(
+                "Lane messages of {} aren't being drained, dropping a message on lane {}",
+                connection.id(),
+                lane
+            );
+            return;
+        }
+
+        self.lane_recv.push((lane, packet));
+    }
+
+    /// Sends every packet with the session's transport and every lane message as its lane
+    /// requires, then drives the streams being opened.
+    fn send(
+        &mut self,
+        connection: &mut QuicConnection,
+        packets: &mut Vec<Bytes>,
+        lanes: Option<&QuicLaneConfig>,
+    ) {
+        let retry = mem::take(&mut self.unordered_retry);
+
+        if connection.is_open() {
+            for packet in retry {
+                self.send_unordered(connection, packet);
+            }
+        } else if !retry.is_empty() {
+            warn!(
+                "{} is closed, {} packets will be dropped",
+                connection.id(),
+                retry.len()
+            );
+        }
+
+        let packets = packets.drain(..).map(|payload| {
+            LaneMessage {
+                lane: None,
+                sequence: None,
+                payload,
+            }
+            .encode()
+        });
+
+        match self.transport {
+            QuicSessionTransport::OrderedStream => {
+                self.ordered.queue(packets);
+            }
+            QuicSessionTransport::UnorderedStreams => {
+                for packet in packets {
+                    self.send_unordered(connection, packet);
+                }
+            }
+            QuicSessionTransport::Datagrams => {
+                send_datagrams(connection, packets);
+            }
+        }
+
+        self.send_lanes(connection, lanes);
+
+        self.ordered.poll(connection);
+        for stream in self.ordered_lanes.values_mut() {
+            stream.poll(connection);
+        }
+        self.poll_unordered();
+
+        if self.finish_requested {
+            let ordered = self
+                .ordered_lanes
+                .values_mut()
+                .chain(iter::once(&mut self.ordered));
+
+            for stream in ordered {
+                if let Some(send) = stream.finish() {
+                    self.finishing.push(send);
+                }
+            }
+        }
+
+        self.finishing.retain(QuicSendStream::is_open);
+    }
+
+    fn send_lanes(
+        &mut self,
+        connection: &mut QuicConnection,
+        lanes: Option<&QuicLaneConfig>,
+    ) {
+        let mut lane_send = mem::take(&mut self.lane_send);
+
+        for (lane, payload) in lane_send.drain(..) {
+            let kind = lanes.and_then(|lanes| lanes.lane(lane));
+
+            let sequence = kind.filter(QuicLaneKind::is_sequenced).map(|_| {
+                let next = self.send_sequences.entry(lane).or_default();
+                let sequence = *next;
+                *next = next.wrapping_add(1);
+                sequence
+            });
+
+            let message = LaneMessage {
+                lane: Some(lane),
+                sequence,
+                payload,
+            }
+            .encode();
+
+            match kind {
+                Some(QuicLaneKind::ReliableOrdered) => {
+                    self.ordered_lanes
+                        .entry(lane)
+                        .or_default()
+                        .queue(iter::once(message));
+                }
+                Some(QuicLaneKind::ReliableUnordered) => {
+                    self.send_unordered(connection, message);
+                }
+                Some(_) if connection.datagrams_enabled() => {
+                    send_datagrams(connection, iter::once(message));
+                }
+                Some(_) => self.send_unordered(connection, message),
+                None => warn!(
+                    "Message sent on unknown lane {} of {}, dropping it",
+                    lane,
+                    connection.id()
+                ),
+            }
+        }
+
+        // Keeps the allocation around for the next frame
+        self.lane_send = lane_send;
+    }
+
+    fn send_unordered(&mut self, connection: &mut QuicConnection, packet: Bytes) {
+        match connection.open_send_stream() {
+            Ok(attempt) => self.unordered.push((attempt, packet)),
+            Err(e) => {
+                warn!(
+                    "Unable to open session stream on {}, retrying next frame: {}",
+                    connection.id(),
+                    e
+                );
+                self.unordered_retry.push(packet);
+            }
+        }
+    }
+
+    fn poll_unordered(&mut self) {
+        let mut unordered = mem::take(&mut self.unordered);
+
+        unordered.retain_mut(|(attempt, packet)| match attempt.attempt_result() {
+            Err(QuicActionError::Pending) => true,
+            Ok(Some(mut send)) => {
+                match send.send_message(mem::take(packet)) {
+                    Ok(()) => {}
+                    Err(SendMessageError::Framing(e)) => {
+                        warn!("Dropping packet on session stream {}: {}", send.id(), e)
+                    }
+                    Err(
+                        SendMessageError::Full(packet) | SendMessageError::Closed(packet),
+                    ) => self.unordered_retry.push(packet),
+                }
+
+                let _ = send.close();
+                self.finishing.push(send);
+                false
+            }
+            Ok(None) | Err(_) => {
+                warn!("Unable to open session stream, retrying next frame");
+                self.unordered_retry.push(mem::take(packet));
+                false
+            }
+        });
+
+        self.unordered = unordered;
+    }
+
+    /// The MTU of the packets the session sends, for streams the payload which fits
+    /// in a single QUIC packet on the current path. Leaves room for the lane tag.
+    fn mtu(
+        &self,
+        connection: &QuicConnection,
+        stats: &QuicConnectionStats,
+    ) -> Option<usize> {
+        let mtu = match self.transport {
+            QuicSessionTransport::Datagrams => connection.max_datagram_size(),
+            _ => (stats.path_mtu > 0).then(|| {
+                (stats.path_mtu as usize).saturating_sub(STREAM_PACKET_OVERHEAD)
+            }),
+        };
+
+        mtu.map(|mtu| mtu.saturating_sub(SESSION_HEADER_LEN).max(MIN_DATAGRAM_MTU))
+    }
+}
+
+/// Sends every packet as a datagram, dropping the rest if one can't be sent.
+fn send_datagrams(
+    connection: &mut QuicConnection,
+    mut packets: impl ExactSizeIterator<Item = Bytes>,
+) {
+    for packet in packets.by_ref() {
+        if let Err(e) = connection.send_datagram(packet) {
+            warn!(
+                "Unable to send datagram for '{}', {} packets will be dropped: {}",
+                connection.id(),
+                packets.len() + 1,
+                e
+            );
+            break;
+        }
+    }
+}
+
+type SessionQueryData = (
+    &'static mut Session,
+    &'static mut QuicConnectionSession,
+    &'static mut QuicConnection,
+);
+
+#[tracing::instrument(skip_all)]
+pub(crate) fn aeronet_connection_recv(
+    query: Query<(SessionQueryData, Option<&QuicLaneConfig>)>,
+) {
+    for ((mut session, mut connection_session, mut connection), lanes) in query {
+        connection_session.recv(&mut connection, &mut session.recv, lanes);
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) fn aeronet_connection_send(
+    query: Query<(
+        SessionQueryData,
+        &QuicConnectionStats,
+        Option<&QuicLaneConfig>,
+    )>,
+) {
+    for ((mut session, mut connection_session, mut connection), stats, lanes) in query {
+        if let Some(mtu) = connection_session.mtu(&connection, stats)
+            && mtu != session.mtu()
+        {
+            let _ = session.set_mtu(mtu);
+        }
+
+        connection_session.send(&mut connection, &mut session.send, lanes);
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::ecs::{component::Component, entity::Entity, event::EntityEvent};
+
+use crate::common::{attempt::QuicActionError, connection::id::ConnectionId};
+
+/// Where a connection entity is in its lifecycle.
+///
+/// Added along with a [QuicConnectionAttempt][crate::common::connection::QuicConnectionAttempt]
+/// or [QuicConnection][crate::common::connection::QuicConnection] and kept up to date by
+/// the [ConnectionAttemptPlugin][crate::common::connection::plugin::ConnectionAttemptPlugin]
+/// and [DisconnectHandlerPlugin][crate::common::plugin::DisconnectHandlerPlugin].
+///
+/// Like aeronet, connection entities get a [SessionEndpoint][aeronet_io::SessionEndpoint]
+/// as soon as they start connecting.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum QuicConnectionState {
+    /// The connection attempt is still pending.
+    #[default]
+    Connecting,
+    /// The connection has been made, but the peer hasn't confirmed the handshake yet.
+    /// Streams and datagrams can already be used.
+    Handshaking,
+    /// The handshake has been confirmed, [QuicConnected] has been triggered.
+    Connected,
+    /// The connection is shutting down, [QuicClosing] has been triggered.
+    Closing,
+    /// The connection attempt failed, or the connection has been closed and
+    /// its [QuicConnection][crate::common::connection::QuicConnection] removed.
+    Closed,
+}
+
+impl QuicConnectionState {
+    /// Returns `true` if the connection can be used to send and receive data.
+    pub fn is_connected(&self) -> bool {
+        matches!(self, Self::Handshaking | Self::Connected)
+    }
+
+    /// Returns `true` if the connection is closing or has been closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Self::Closing | Self::Closed)
+    }
+}
+
+/// Triggered on a connection entity once the peer has confirmed the handshake.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicConnected {
+    pub entity: Entity,
+    pub connection_id: ConnectionId,
+}
+
+/// Triggered on a connection entity when its
+/// [QuicConnectionAttempt][crate::common::connection::QuicConnectionAttempt] fails.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicConnectionFailed {
+    pub entity: Entity,
+    pub error: QuicActionError,
+}
+
+/// Triggered on a connection entity when the connection starts shutting down, either
+/// because it was closed locally or the connection task is finishing.
+///
+/// Once it has closed aeronet's [Disconnected][aeronet_io::connection::Disconnected]
+/// is triggered with the reason.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicClosing {
+    pub entity: Entity,
+    pub connection_id: ConnectionId,
+}
@@ -0,0 +1,294 @@
+use bevy::{
+    app::{Plugin, PreUpdate},
+    ecs::{component::Component, schedule::IntoScheduleConfigs, system::Query},
+    log::tracing,
+};
+use s2n_quic::provider::event::{
+    ConnectionInfo, ConnectionMeta, Subscriber,
+    events::{
+        AckRangeReceived, MtuUpdated, PacketHeader, PacketLost, PacketReceived,
+        PacketSent, RecoveryMetrics,
+    },
+};
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{async_plugin::quic_tick_ready, common::connection::QuicConnection};
+
+/// How many disjoint ranges of acknowledged packets are tracked per packet space, older
+/// ranges are forgotten once there are more as peers only repeat their latest ranges.
+const MAX_ACK_RANGES: usize = 64;
+
+/// A snapshot of the health of a [QuicConnection].
+///
+/// Every [QuicConnection] entity gets this component, the [ConnectionStatsPlugin]
+/// keeps it up to date once per frame. RTT and congestion values are for the
+/// currently active path, counters are totals over the life of the connection.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuicConnectionStats {
+    /// The exponentially weighted average round trip time.
+    pub smoothed_rtt: Duration,
+    /// The lowest round trip time seen so far.
+    pub min_rtt: Duration,
+    /// The round trip time of the most recent acknowledged packet.
+    pub latest_rtt: Duration,
+    /// The mean deviation of the round trip time, a measure of jitter.
+    pub rtt_variance: Duration,
+    /// How many bytes the congestion controller allows to be in flight.
+    pub congestion_window: u32,
+    /// How many sent bytes are still waiting to be acknowledged.
+    pub bytes_in_flight: u32,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub packets_lost: u64,
+    pub packets_acked: u64,
+    /// Total bytes sent, including QUIC packet overhead.
+    pub bytes_sent: u64,
+    /// Total bytes received, including QUIC packet overhead.
+    pub bytes_received: u64,
+    /// Total bytes declared lost and queued for retransmission.
+    pub bytes_lost: u64,
+    /// The current maximum packet size of the path, `0` until it is known.
+    pub path_mtu: u16,
+}
+
+impl QuicConnectionStats {
+    /// The fraction of sent packets which have been lost, from `0.0` to `1.0`.
+    pub fn loss_rate(&self) -> f32 {
+        if self.packets_sent == 0 {
+            return 0.0;
+        }
+
+        self.packets_lost as f32 / self.packets_sent as f32
+    }
+}
+
+/// The plugin which copies the latest [QuicConnectionStats] from the
+/// async side onto every [QuicConnection] entity.
+#[derive(Debug)]
+pub struct ConnectionStatsPlugin;
+
+impl Plugin for ConnectionStatsPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app.add_systems(PreUpdate, update_connection_stats.run_if(quic_tick_ready));
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn update_connection_stats(query: Query<(&QuicConnection, &mut QuicConnectionStats)>) {
+    for (connection, mut stats) in query {
+        let Some(latest) = connection.stats() else {
+            continue;
+        };
+
+        if *stats != latest {
+            *stats = latest;
+        }
+    }
+}
+
+/// Event subscriber which aggregates [QuicConnectionStats] for every connection.
+#[derive(Debug, Default)]
+pub(crate) struct StatsSubscriber;
+
+#[derive(Debug, Default)]
+pub(crate) struct StatsContext {
+    stats: QuicConnectionStats,
+    /// Acknowledged packet numbers for the initial, handshake and application packet
+    /// spaces, ACK frames repeat ranges so only packets not in here are counted.
+    acked: [AckedPackets; 3],
+}
+
+impl StatsContext {
+    pub(crate) fn stats(&self) -> QuicConnectionStats {
+        self.stats
+    }
+}
+
+impl Subscriber for StatsSubscriber {
+    type ConnectionContext = StatsContext;
+
+    fn create_connection_context(
+        &mut self,
+        _meta: &ConnectionMeta,
+        _info: &ConnectionInfo,
+    ) -> Self::ConnectionContext {
+        StatsContext::default()
+    }
+
+    fn on_packet_sent(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &PacketSent,
+    ) {
+        context.stats.packets_sent += 1;
+        context.stats.bytes_sent += event.packet_len as u64;
+    }
+
+    fn on_packet_received(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &PacketReceived,
+    ) {
+        context.stats.packets_received += 1;
+        context.stats.bytes_received += event.packet_len as u64;
+    }
+
+    fn on_packet_lost(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &PacketLost,
+    ) {
+        context.stats.packets_lost += 1;
+        context.stats.bytes_lost += event.bytes_lost as u64;
+    }
+
+    fn on_ack_range_received(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &AckRangeReceived,
+    ) {
+        let space = match event.packet_header {
+            PacketHeader::Initial { .. } => 0,
+            PacketHeader::Handshake { .. } => 1,
+            PacketHeader::ZeroRtt { .. } | PacketHeader::OneRtt { .. } => 2,
+            _ => return,
+        };
+
+        let newly_acked =
+            context.acked[space].insert(*event.ack_range.start(), *event.ack_range.end());
+
+        context.stats.packets_acked += newly_acked;
+    }
+
+    fn on_recovery_metrics(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &RecoveryMetrics,
+    ) {
+        if !event.path.is_active {
+            return;
+        }
+
+        let stats = &mut context.stats;
+        stats.smoothed_rtt = event.smoothed_rtt;
+        stats.min_rtt = event.min_rtt;
+        stats.latest_rtt = event.latest_rtt;
+        stats.rtt_variance = event.rtt_variance;
+        stats.congestion_window = event.congestion_window;
+        stats.bytes_in_flight = event.bytes_in_flight;
+    }
+
+    fn on_mtu_updated(
+        &mut self,
+        context: &mut Self::ConnectionContext,
+        _meta: &ConnectionMeta,
+        event: &MtuUpdated,
+    ) {
+        context.stats.path_mtu = event.mtu;
+    }
+}
+
+/// The set of acknowledged packet numbers of a packet space, as disjoint inclusive ranges.
+#[derive(Debug, Default)]
+struct AckedPackets {
+    /// Maps the start of each range to its end.
+    ranges: BTreeMap<u64, u64>,
+    /// Everything at or below this has been forgotten and is treated as acknowledged.
+    floor: Option<u64>,
+}
+
+impl AckedPackets {
+    /// Adds the range, returning how many of its packets weren't acknowledged before.
+    fn insert(&mut self, start: u64, end: u64) -> u64 {
+        let start = match self.floor {
+            Some(floor) if floor >= end => return 0,
+            Some(floor) => start.max(floor + 1),
+            None => start,
+        };
+
+        // Ranges which overlap or touch the new one are merged into it
+        let merged: Vec<(u64, u64)> = self
+            .ranges
+            .range(..=end.saturating_add(1))
+            .rev()
+            .take_while(|(_, range_end)| range_end.saturating_add(1) >= start)
+            .map(|(range_start, range_end)| (*range_start, *range_end))
+            .collect();
+
+        let mut already_acked = 0;
+        let (mut merged_start, mut merged_end) = (start, end);
+
+        for (range_start, range_end) in merged {
+            let overlap_start = range_start.max(start);
+            let overlap_end = range_end.min(end);
+
+            if overlap_start <= overlap_end {
+                already_acked += overlap_end - overlap_start + 1;
+            }
+
+            merged_start = merged_start.min(range_start);
+            merged_end = merged_end.max(range_end);
+            self.ranges.remove(&range_start);
+        }
+
+        self.ranges.insert(merged_start, merged_end);
+
+        while self.ranges.len() > MAX_ACK_RANGES {
+            if let Some((_, oldest_end)) = self.ranges.pop_first() {
+                self.floor = Some(oldest_end);
+            }
+        }
+
+        end - start + 1 - already_acked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_every_range_of_an_ack() {
+        let mut acked = AckedPackets::default();
+
+        // s2n reports the ranges of an ACK frame from highest to lowest
+        let counted: u64 = [(10, 12), (5, 7), (1, 3)]
+            .into_iter()
+            .map(|(start, end)| acked.insert(start, end))
+            .sum();
+
+        assert_eq!(counted, 9);
+    }
+
+    #[test]
+    fn repeated_ranges_are_counted_once() {
+        let mut acked = AckedPackets::default();
+
+        assert_eq!(acked.insert(5, 7), 3);
+        assert_eq!(acked.insert(1, 3), 3);
+
+        // The next ACK repeats both ranges, fills the gap and acknowledges a new packet
+        assert_eq!(acked.insert(1, 8), 2);
+        assert_eq!(acked.insert(1, 8), 0);
+        assert_eq!(acked.ranges.len(), 1);
+    }
+
+    #[test]
+    fn forgotten_ranges_are_not_counted_again() {
+        let mut acked = AckedPackets::default();
+
+        for packet in (0..=MAX_ACK_RANGES as u64).map(|i| i * 2) {
+            assert_eq!(acked.insert(packet, packet), 1);
+        }
+
+        assert_eq!(acked.ranges.len(), MAX_ACK_RANGES);
+        // Packet 0 was forgotten but still counts as acknowledged, 1 never was
+        assert_eq!(acked.insert(0, 0), 0);
+        assert_eq!(acked.insert(0, 1), 1);
+    }
+}
@@ -3,40 +3,41 @@ use bevy::log::{
     tracing::{self},
     warn,
 };
+use bytes::Bytes;
 use s2n_quic::{
     Connection, application,
     connection::{Error as ConnectionError, Handle as ConnectionHandle},
-    stream::PeerStream,
+    provider::event::Location,
+    stream::{PeerStream, SendStream},
 };
-use std::{error::Error, fmt, net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::VecDeque, error::Error, fmt, net::SocketAddr, sync::Arc};
 use thiserror::Error;
 use tokio::{
-    runtime::Handle,
     select,
     sync::{
         mpsc::{self, error::TrySendError},
         oneshot,
     },
-    time::timeout,
 };
 
 use crate::common::{
     attempt::TaskError,
     connection::{
         ConnectionResponse,
+        control::{
+            ControlEvent, PeerControlStream, peer_control_stream_id, send_close_reason,
+            start_control_stream,
+        },
         disconnect::{ConnectionDisconnectReason, ConnectionErrorDisconnected},
         id::ConnectionId,
         open_flag::OpenFlag,
         stream_flag::StreamFlag,
     },
+    orchestrator::AsyncOrchestrator,
     stream::{QuicPeerStream, receive::QuicReceiveStream, send::QuicSendStream},
     task_state::QuicTaskState,
 };
 
-/// Timeout used when the buffered stream type doesn't match what the command
-/// asked for, so we do a short poll to see if the right type is available.
-const ACCEPT_MISMATCH_TIMEOUT: Duration = Duration::from_millis(1);
-
 pub(in crate::common::connection) type ConnectionTaskState =
     QuicTaskState<ConnectionDisconnectReason>;
 
@@ -50,8 +51,29 @@ pub(crate) enum ConnectionCommand {
     },
     Accept {
         respond_to: oneshot::Sender<ConnectionResponse<QuicPeerStream>>,
+        filter: PeerStreamFilter,
     },
     Close(application::Error),
+    /// Sends the reason to the peer on the control stream before closing.
+    CloseWithReason(application::Error, String),
+}
+
+/// Which kinds of peer stream an accept hands over, any other stream is refused
+/// by resetting and stopping it with the refuse code.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PeerStreamFilter {
+    pub(crate) bidirectional: bool,
+    pub(crate) unidirectional: bool,
+    pub(crate) refuse_code: application::Error,
+}
+
+impl PeerStreamFilter {
+    pub(crate) fn accepts(&self, stream: &PeerStream) -> bool {
+        match stream {
+            PeerStream::Bidirectional(_) => self.bidirectional,
+            PeerStream::Receive(_) => self.unidirectional,
+        }
+    }
 }
 
 // TODO: This could be made public and used elsewhere as a async way to open new connections
@@ -62,6 +84,7 @@ pub(crate) struct ConnectionHandleTask {
     is_open: OpenFlag,
     remote_addr: Result<SocketAddr, ConnectionError>,
     connection_id: ConnectionId,
+    orchestrator: AsyncOrchestrator,
 }
 
 impl fmt::Display for ConnectionHandleTask {
@@ -81,6 +104,7 @@ impl ConnectionHandleTask {
         connection: ConnectionHandle,
         is_open: OpenFlag,
         connection_id: ConnectionId,
+        orchestrator: AsyncOrchestrator,
     ) -> Self {
         let remote_addr = connection.remote_addr();
 
@@ -89,6 +113,7 @@ impl ConnectionHandleTask {
             is_open,
             remote_addr,
             connection_id,
+            orchestrator,
         }
     }
 
@@ -105,12 +130,12 @@ impl ConnectionHandleTask {
                 let (rec_stream, send_stream) = stream.split();
 
                 let quic_send = QuicSendStream::new(
-                    Handle::current(),
+                    &self.orchestrator,
                     send_stream,
                     self.connection_id.parent_id(),
                 );
                 let quic_rec = QuicReceiveStream::new(
-                    Handle::current(),
+                    &self.orchestrator,
                     rec_stream,
                     self.connection_id.parent_id(),
                 );
@@ -130,7 +155,7 @@ impl ConnectionHandleTask {
         match send_res {
             Ok(stream) => {
                 let quic_send = QuicSendStream::new(
-                    Handle::current(),
+                    &self.orchestrator,
                     stream,
                     self.connection_id.parent_id(),
                 );
@@ -149,8 +174,21 @@ pub(crate) struct ConnectionTask {
     is_open: OpenFlag,
     pending_stream: Arc<StreamFlag>,
     connection_id: ConnectionId,
-    /// Holds a stream that arrived before a matching command was ready to consume it.
-    buffered_stream: Option<PeerStream>,
+    /// Streams that arrived before a matching command was ready to consume them, along
+    /// with anything already read from them.
+    ///
+    /// Streams stay open while they're queued, so the peer's stream limit bounds this.
+    buffered_streams: VecDeque<(PeerStream, Option<Bytes>)>,
+    /// Our control stream, the close reason is sent on it.
+    control_stream: Option<SendStream>,
+    /// The control stream of the peer, until it has been found or has finished.
+    peer_control_stream: Option<PeerControlStream>,
+    /// The ID of the peer's control stream, until that stream has arrived.
+    peer_control_id: Option<u64>,
+    /// The reason the peer sent before closing the connection.
+    peer_close_reason: Option<String>,
+    /// Accepted streams are run on the same orchestrator as the connection
+    orchestrator: AsyncOrchestrator,
 }
 
 impl ConnectionTask {
@@ -160,7 +198,16 @@ impl ConnectionTask {
         connection_id: ConnectionId,
         is_open: OpenFlag,
         pending_stream: Arc<StreamFlag>,
+        control_stream: Option<SendStream>,
+        orchestrator: AsyncOrchestrator,
     ) -> Self {
+        // Both sides open their control stream first, so the peer's differs from ours
+        // only in which side opened it
+        let peer_control_id = match &control_stream {
+            Some(stream) => stream.id() ^ 1,
+            None => peer_control_stream_id(connection_id.parent_id().connection_type()),
+        };
+
         Self {
             connection,
             cmd_receiver,
@@ -168,7 +215,12 @@ impl ConnectionTask {
             is_open,
             pending_stream,
             connection_id,
-            buffered_stream: None,
+            buffered_streams: VecDeque::new(),
+            control_stream,
+            peer_control_stream: None,
+            peer_control_id: Some(peer_control_id),
+            peer_close_reason: None,
+            orchestrator,
         }
     }
 
@@ -183,66 +235,57 @@ impl ConnectionTask {
     pub(crate) async fn start(mut self) -> ConnectionDisconnectReason {
         info!("New connection opened");
 
+        if let Some(stream) = &mut self.control_stream
+            && !start_control_stream(stream).await
+        {
+            self.control_stream = None;
+        }
+
         while self.disconnect_flag.is_none() {
-            // If we have a buffered stream, we only need to wait for a command
-            // that will consume it.
-            if self.buffered_stream.is_some() {
-                self.pending_stream.set_true();
-                match self.cmd_receiver.recv().await {
-                    Some(cmd) => {
-                        let res = self.handle_command(cmd).await;
-                        self.handle_cmd_result(res).await;
-                    }
-                    None => {
-                        self.disconnect_flag =
-                            Some(ConnectionDisconnectReason::MspcChannelClosed {
-                                channel_name: "Connection command channel".into(),
-                            });
-                    }
-                }
-            } else {
-                // No buffered stream: race commands against an incoming stream.
-                select! {
-                    biased;
-
-                    cmd_opt = self.cmd_receiver.recv() => {
-                        match cmd_opt {
-                            Some(cmd) => {
-                                let res = self.handle_command(cmd).await;
-                                self.handle_cmd_result(res).await;
-                            }
-                            None => {
-                                self.disconnect_flag = Some(
-                                    ConnectionDisconnectReason::MspcChannelClosed {
-                                        channel_name: "Connection command channel".into(),
-                                    },
-                                );
-                            }
+            select! {
+                biased;
+
+                cmd_opt = self.cmd_receiver.recv() => {
+                    match cmd_opt {
+                        Some(cmd) => self.handle_command(cmd),
+                        None => {
+                            self.disconnect_flag = Some(
+                                ConnectionDisconnectReason::MspcChannelClosed {
+                                    channel_name: "Connection command channel".into(),
+                                },
+                            );
                         }
                     }
+                }
 
-                    accept_res = self.connection.accept() => {
-                        match accept_res {
-                            Ok(Some(stream)) => {
-                                // Buffer it, the next command will consume it.
-                                self.buffered_stream = Some(stream);
-                            }
-                            Ok(None) => {
-                                self.disconnect_flag = Some(
-                                    ConnectionDisconnectReason::PeerClosed
-                                );
-                            }
-                            Err(err) => {
-                                if err.is_closed() {
-                                    self.is_open.set_closed();
-                                }
-                                self.disconnect_flag =
-                                    Some(ConnectionDisconnectReason::ConnectionError(err));
+                // Read before accepting, so a reason sent just before a close is kept
+                event = read_control(&mut self.peer_control_stream),
+                    if self.peer_control_stream.is_some() =>
+                {
+                    self.handle_control_event(event);
+                }
+
+                accept_res = self.connection.accept() => {
+                    match accept_res {
+                        Ok(Some(stream)) => self.on_peer_stream(stream),
+                        Ok(None) => {
+                            self.disconnect_flag = Some(
+                                ConnectionDisconnectReason::PeerClosed
+                            );
+                        }
+                        Err(err) => {
+                            if err.is_closed() {
+                                self.is_open.set_closed();
                             }
+                            self.disconnect_flag = Some(self.connection_error_reason(err));
                         }
                     }
                 }
             }
+
+            if !self.buffered_streams.is_empty() {
+                self.pending_stream.set_true();
+            }
         }
 
         self.disconnect_flag
@@ -251,205 +294,201 @@ impl ConnectionTask {
             )))
     }
 
-    async fn handle_command(
-        &mut self,
-        cmd: ConnectionCommand,
-    ) -> Result<(), ConnectionError> {
-        match cmd {
-            ConnectionCommand::Accept { respond_to } => {
-                if let Some(stream) = self.buffered_stream.take() {
-                    let peer_stream = QuicPeerStream::new(
-                        Handle::current(),
-                        stream,
-                        self.connection_id.parent_id(),
-                    );
-                    if respond_to.send(Ok(Some(peer_stream))).is_err() {
-                        warn!(
-                            "Accept response handler closed before stream could be sent."
-                        );
-                    }
-                    Ok(())
-                } else {
-                    let _ = respond_to.send(Ok(None));
-                    Ok(())
-                }
-            }
-
-            ConnectionCommand::AcceptReceive { respond_to } => {
-                match self.buffered_stream.take() {
-                    Some(PeerStream::Receive(stream)) => {
-                        let rec = QuicReceiveStream::new(
-                            Handle::current(),
-                            stream,
-                            self.connection_id.parent_id(),
-                        );
-                        if respond_to.send(Ok(Some(rec))).is_err() {
-                            warn!(
-                                "Accept receive response handler closed before stream \
-                                could be sent."
-                            );
-                        }
-                        Ok(())
-                    }
-                    Some(other) => {
-                        // Wrong type, put it back and do a short poll for the right one.
-                        self.buffered_stream = Some(other);
-                        self.accept_receive(respond_to).await
-                    }
-                    None => self.accept_receive(respond_to).await,
-                }
+    /// Buffers a stream opened by the peer, unless it's the peer's control stream.
+    fn on_peer_stream(&mut self, stream: PeerStream) {
+        match stream {
+            PeerStream::Receive(stream) if self.peer_control_id == Some(stream.id()) => {
+                self.peer_control_id = None;
+                self.peer_control_stream = Some(PeerControlStream::new(stream));
             }
+            stream => self.buffered_streams.push_back((stream, None)),
+        }
+    }
 
-            ConnectionCommand::AcceptBidirectional { respond_to } => {
-                match self.buffered_stream.take() {
-                    Some(PeerStream::Bidirectional(stream)) => {
-                        let (rec, send) = stream.split();
-                        let rec = QuicReceiveStream::new(
-                            Handle::current(),
-                            rec,
-                            self.connection_id.parent_id(),
-                        );
-                        let send = QuicSendStream::new(
-                            Handle::current(),
-                            send,
-                            self.connection_id.parent_id(),
-                        );
-                        if respond_to.send(Ok(Some((rec, send)))).is_err() {
-                            warn!(
-                                "Accept bidir response handler closed before stream \
-                                could be sent."
-                            );
-                        }
-                        Ok(())
-                    }
-                    Some(other) => {
-                        self.buffered_stream = Some(other);
-                        self.accept_bidirectional(respond_to).await
-                    }
-                    None => self.accept_bidirectional(respond_to).await,
-                }
-            }
+    fn handle_control_event(&mut self, event: ControlEvent) {
+        let Some(control) = self.peer_control_stream.take() else {
+            return;
+        };
 
-            ConnectionCommand::Close(code) => {
-                self.connection.close(code);
-                Ok(())
+        match event {
+            ControlEvent::CloseReason(reason) => self.peer_close_reason = Some(reason),
+            ControlEvent::NotControl => {
+                // The peer doesn't reserve a control stream, so it's the first of its own
+                let (stream, received) = control.into_peer_stream();
+                self.buffered_streams
+                    .push_front((PeerStream::Receive(stream), received));
             }
+            ControlEvent::Closed => {}
         }
     }
 
-    async fn accept_receive(
-        &mut self,
-        respond_to: oneshot::Sender<ConnectionResponse<QuicReceiveStream>>,
-    ) -> Result<(), ConnectionError> {
-        let res = timeout(
-            ACCEPT_MISMATCH_TIMEOUT,
-            self.connection.accept_receive_stream(),
-        )
-        .await;
+    fn handle_command(&mut self, cmd: ConnectionCommand) {
+        match cmd {
+            // A cancelled or timed out accept leaves any buffered stream for the next one
+            ConnectionCommand::Accept { respond_to, .. } if respond_to.is_closed() => {}
+            ConnectionCommand::AcceptReceive { respond_to } if respond_to.is_closed() => {
+            }
+            ConnectionCommand::AcceptBidirectional { respond_to }
+                if respond_to.is_closed() => {}
 
-        let Ok(accept_res) = res else {
-            let _ = respond_to.send(Ok(None));
-            return Ok(());
-        };
+            ConnectionCommand::Accept { respond_to, filter } => {
+                let Some((stream, received)) = self.buffered_streams.pop_front() else {
+                    let _ = respond_to.send(Ok(None));
+                    return;
+                };
 
-        match accept_res {
-            Ok(opt) => {
-                let mapped = opt.map(|s| {
-                    QuicReceiveStream::new(
-                        Handle::current(),
-                        s,
-                        self.connection_id.parent_id(),
-                    )
-                });
-                if respond_to.send(Ok(mapped)).is_err() {
+                if !filter.accepts(&stream) {
+                    refuse_stream(stream, filter.refuse_code);
+                    let _ = respond_to.send(Ok(None));
+                    return;
+                }
+
+                let peer_stream = QuicPeerStream::with_received(
+                    &self.orchestrator,
+                    stream,
+                    self.connection_id.parent_id(),
+                    received,
+                );
+                if respond_to.send(Ok(Some(peer_stream))).is_err() {
+                    warn!("Accept response handler closed before stream could be sent.");
+                }
+            }
+
+            ConnectionCommand::AcceptReceive { respond_to } => {
+                let Some((PeerStream::Receive(stream), received)) =
+                    self.take_buffered(|s| matches!(s, PeerStream::Receive(_)))
+                else {
+                    let _ = respond_to.send(Ok(None));
+                    return;
+                };
+
+                let rec = QuicReceiveStream::with_received(
+                    &self.orchestrator,
+                    stream,
+                    self.connection_id.parent_id(),
+                    received,
+                );
+                if respond_to.send(Ok(Some(rec))).is_err() {
                     warn!(
-                        "Accept receive stream opened but response handler was already \
-                        closed."
+                        "Accept receive response handler closed before stream could be \
+                        sent."
                     );
                 }
-                Ok(())
             }
-            Err(err) => {
-                if respond_to
-                    .send(Err(TaskError::ConnectionFailed(err)))
-                    .is_err()
-                {
+
+            ConnectionCommand::AcceptBidirectional { respond_to } => {
+                let Some((PeerStream::Bidirectional(stream), _)) =
+                    self.take_buffered(|s| matches!(s, PeerStream::Bidirectional(_)))
+                else {
+                    let _ = respond_to.send(Ok(None));
+                    return;
+                };
+
+                let (rec, send) = stream.split();
+                let rec = QuicReceiveStream::new(
+                    &self.orchestrator,
+                    rec,
+                    self.connection_id.parent_id(),
+                );
+                let send = QuicSendStream::new(
+                    &self.orchestrator,
+                    send,
+                    self.connection_id.parent_id(),
+                );
+                if respond_to.send(Ok(Some((rec, send)))).is_err() {
                     warn!(
-                        "Accept receive errored and response handler was already closed."
+                        "Accept bidir response handler closed before stream could be sent."
                     );
                 }
-                if err.is_closed() {
-                    self.is_open.set_closed();
+            }
+
+            ConnectionCommand::Close(code) => self.connection.close(code),
+
+            ConnectionCommand::CloseWithReason(code, reason) => {
+                match self.control_stream.take() {
+                    Some(stream) => {
+                        // Closes the connection once the reason is sent, the accept
+                        // above then ends this task
+                        self.orchestrator.runtime().spawn(send_close_reason(
+                            stream,
+                            self.connection.handle(),
+                            code,
+                            reason,
+                        ));
+                    }
+                    None => self.connection.close(code),
                 }
-                Err(err)
             }
         }
     }
 
-    async fn accept_bidirectional(
+    /// Takes the oldest buffered stream of the kind asked for.
+    fn take_buffered(
         &mut self,
-        respond_to: oneshot::Sender<
-            ConnectionResponse<(QuicReceiveStream, QuicSendStream)>,
-        >,
-    ) -> Result<(), ConnectionError> {
-        let res = timeout(
-            ACCEPT_MISMATCH_TIMEOUT,
-            self.connection.accept_bidirectional_stream(),
-        )
-        .await;
+        kind: impl Fn(&PeerStream) -> bool,
+    ) -> Option<(PeerStream, Option<Bytes>)> {
+        let index = self
+            .buffered_streams
+            .iter()
+            .position(|(stream, _)| kind(stream))?;
+
+        self.buffered_streams.remove(index)
+    }
 
-        let Ok(accept_res) = res else {
-            let _ = respond_to.send(Ok(None));
-            return Ok(());
-        };
+    /// Uses the close reason sent by the peer if the peer closed the connection.
+    fn connection_error_reason(
+        &mut self,
+        err: ConnectionError,
+    ) -> ConnectionDisconnectReason {
+        // The reason arrives before the close, but may not have been read yet
+        if let Some(event) = self
+            .peer_control_stream
+            .as_mut()
+            .and_then(PeerControlStream::next_now)
+        {
+            self.handle_control_event(event);
+        }
 
-        match accept_res {
-            Ok(opt) => {
-                let mapped = opt.map(|bidir| {
-                    let (rec, send) = bidir.split();
-                    let rec = QuicReceiveStream::new(
-                        Handle::current(),
-                        rec,
-                        self.connection_id.parent_id(),
-                    );
-                    let send = QuicSendStream::new(
-                        Handle::current(),
-                        send,
-                        self.connection_id.parent_id(),
-                    );
-                    (rec, send)
-                });
-                if respond_to.send(Ok(mapped)).is_err() {
-                    warn!(
-                        "Accept bidir stream opened but response handler was already \
-                        closed."
-                    );
-                }
-                Ok(())
-            }
-            Err(err) => {
-                if respond_to
-                    .send(Err(TaskError::ConnectionFailed(err)))
-                    .is_err()
-                {
-                    warn!(
-                        "Accept bidir errored and response handler was already closed."
-                    );
-                }
-                if err.is_closed() {
-                    self.is_open.set_closed();
-                }
-                Err(err)
-            }
+        match (err, self.peer_close_reason.take()) {
+            (
+                ConnectionError::Application {
+                    error,
+                    initiator: Location::Remote,
+                    ..
+                },
+                Some(reason),
+            ) => ConnectionDisconnectReason::PeerDisconnected {
+                code: error.into(),
+                reason,
+            },
+            (err, _) => ConnectionDisconnectReason::ConnectionError(err),
         }
     }
+}
+
+/// Reads the peer's control stream, never finishes if there isn't one.
+async fn read_control(control: &mut Option<PeerControlStream>) -> ControlEvent {
+    match control {
+        Some(control) => control.next().await,
+        None => std::future::pending().await,
+    }
+}
 
-    async fn handle_cmd_result(&mut self, cmd_res: Result<(), ConnectionError>) {
-        let Err(err) = cmd_res else {
-            return;
-        };
+/// Refuses a peer stream, the peer sees its send half reset with the code.
+fn refuse_stream(stream: PeerStream, code: application::Error) {
+    let res = match stream {
+        PeerStream::Bidirectional(mut stream) => {
+            info!("Refusing bidirectional peer stream {}", stream.id());
+            stream.stop_sending(code).and_then(|_| stream.reset(code))
+        }
+        PeerStream::Receive(mut stream) => {
+            info!("Refusing receive peer stream {}", stream.id());
+            stream.stop_sending(code)
+        }
+    };
 
-        self.disconnect_flag = Some(ConnectionDisconnectReason::ConnectionError(err));
+    if let Err(err) = res {
+        warn!("Unable to refuse peer stream: {}", err);
     }
 }
 
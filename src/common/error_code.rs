@@ -0,0 +1,253 @@
+use s2n_quic::application;
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::RangeInclusive,
+    sync::{LazyLock, RwLock},
+};
+use thiserror::Error as ThisError;
+
+/// Application error codes in this range are reserved for the crate's own codes.
+pub const RESERVED_CODES: RangeInclusive<u64> = 0x3000..=0x30FF;
+
+/// The largest value a QUIC variable length integer can hold.
+const MAX_CODE: u64 = (1 << 62) - 1;
+
+/// Every registered code with its name, shared by every endpoint in the process.
+static REGISTRY: LazyLock<RwLock<HashMap<u64, &'static str>>> = LazyLock::new(|| {
+    RwLock::new(HashMap::from([
+        (QuicErrorCode::NO_ERROR.0, "no error"),
+        (QuicErrorCode::AERONET_DISCONNECT.0, "aeronet disconnect"),
+        (QuicErrorCode::MESSAGE_TOO_LARGE.0, "message too large"),
+        (
+            QuicErrorCode::SERVER_SHUTTING_DOWN.0,
+            "server shutting down",
+        ),
+        (QuicErrorCode::KICKED.0, "kicked"),
+        (QuicErrorCode::PROTOCOL_VIOLATION.0, "protocol violation"),
+        (QuicErrorCode::SERVER_FULL.0, "server full"),
+        (QuicErrorCode::CONNECTION_REFUSED.0, "connection refused"),
+        (QuicErrorCode::STREAM_REFUSED.0, "stream refused"),
+    ]))
+});
+
+/// An application error code sent to the peer when closing a connection, or resetting
+/// or stopping a stream.
+///
+/// Games can give their own codes a name with [register][Self::register()], received
+/// codes are then decoded back into that name, e.g. in the reasons of
+/// [ConnectionDisconnectReason][crate::common::connection::disconnect::ConnectionDisconnectReason]
+/// and [StreamDisconnectReason][crate::common::stream::disconnect::StreamDisconnectReason].
+///
+/// ```ignore
+/// const CHEATING: u64 = 1001;
+///
+/// let cheating = QuicErrorCode::register(CHEATING, "cheating")?;
+/// connection.close(cheating);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuicErrorCode(u64);
+
+impl QuicErrorCode {
+    /// Closes without an error.
+    pub const NO_ERROR: Self = Self(0);
+    /// Sent when aeronet's [Disconnect][aeronet_io::connection::Disconnect] closes a session.
+    pub const AERONET_DISCONNECT: Self = Self(12345);
+    /// Sent when the peer sent a framed message larger than the max message size.
+    pub const MESSAGE_TOO_LARGE: Self = Self(12346);
+    /// Sent to every client when the server shuts down.
+    pub const SERVER_SHUTTING_DOWN: Self = Self(12347);
+    /// Sent when the server removes a client on purpose.
+    pub const KICKED: Self = Self(12348);
+    /// Sent when the peer didn't follow the protocol of the application.
+    pub const PROTOCOL_VIOLATION: Self = Self(12349);
+    /// Sent when the server already has as many connections as it allows.
+    pub const SERVER_FULL: Self = Self(12350);
+    /// Sent when the server doesn't accept connections from the peer's address.
+    pub const CONNECTION_REFUSED: Self = Self(12351);
+    /// Sent when a peer stream is refused by the [AcceptPolicy][crate::common::stream::acceptor::AcceptPolicy]
+    /// of its connection.
+    pub const STREAM_REFUSED: Self = Self(12352);
+
+    /// Creates a code without registering it.
+    pub fn new(code: u64) -> Result<Self, QuicErrorCodeError> {
+        if code > MAX_CODE {
+            return Err(QuicErrorCodeError::TooLarge(code));
+        }
+
+        Ok(Self(code))
+    }
+
+    /// Registers a named application code for the whole process.
+    ///
+    /// Fails if the code is in [RESERVED_CODES], or if the code or the name has already
+    /// been registered for something else. Registering the same code and name twice is fine.
+    pub fn register(code: u64, name: &'static str) -> Result<Self, QuicErrorCodeError> {
+        let error_code = Self::new(code)?;
+
+        if RESERVED_CODES.contains(&code) {
+            return Err(QuicErrorCodeError::Reserved(code));
+        }
+
+        let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(existing) = registry.get(&code) {
+            if *existing == name {
+                return Ok(error_code);
+            }
+
+            return Err(QuicErrorCodeError::CodeTaken {
+                code,
+                name: existing,
+            });
+        }
+
+        if let Some((existing, _)) = registry.iter().find(|(_, n)| **n == name) {
+            return Err(QuicErrorCodeError::NameTaken {
+                name,
+                code: *existing,
+            });
+        }
+
+        registry.insert(code, name);
+
+        Ok(error_code)
+    }
+
+    /// Looks up a registered code by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+
+        registry
+            .iter()
+            .find(|(_, n)| **n == name)
+            .map(|(code, _)| Self(*code))
+    }
+
+    /// The name the code was registered with, if any.
+    pub fn name(&self) -> Option<&'static str> {
+        let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+        registry.get(&self.0).copied()
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns `true` if this is one of the crate's own codes.
+    pub fn is_reserved(&self) -> bool {
+        RESERVED_CODES.contains(&self.0)
+    }
+}
+
+impl fmt::Display for QuicErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name} ({})", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl From<application::Error> for QuicErrorCode {
+    fn from(error: application::Error) -> Self {
+        Self(error.into())
+    }
+}
+
+impl From<QuicErrorCode> for application::Error {
+    fn from(code: QuicErrorCode) -> Self {
+        // Codes can only be created within the variable length integer range
+        Self::try_from(code.0).expect("Error code exceeds the largest QUIC varint")
+    }
+}
+
+impl From<u32> for QuicErrorCode {
+    fn from(code: u32) -> Self {
+        Self(code as u64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum QuicErrorCodeError {
+    #[error("Error code {0} is reserved by bevy-s2n-quic")]
+    Reserved(u64),
+    #[error("Error code {0} is larger than a QUIC variable length integer can hold")]
+    TooLarge(u64),
+    #[error("Error code {code} is already registered as \"{name}\"")]
+    CodeTaken { code: u64, name: &'static str },
+    #[error("The name \"{name}\" is already registered for error code {code}")]
+    NameTaken { name: &'static str, code: u64 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The registry is shared by the whole process, so every test uses its own codes and names
+
+    #[test]
+    fn refuses_reserved_and_oversized_codes() {
+        assert_eq!(
+            QuicErrorCode::register(0x3000, "reserved start"),
+            Err(QuicErrorCodeError::Reserved(0x3000))
+        );
+        assert_eq!(
+            QuicErrorCode::register(0x30FF, "reserved end"),
+            Err(QuicErrorCodeError::Reserved(0x30FF))
+        );
+        assert_eq!(
+            QuicErrorCode::register(MAX_CODE + 1, "too large"),
+            Err(QuicErrorCodeError::TooLarge(MAX_CODE + 1))
+        );
+        assert!(QuicErrorCode::register(0x3100, "after reserved").is_ok());
+    }
+
+    #[test]
+    fn refuses_taken_codes_and_names() {
+        QuicErrorCode::register(1001, "cheating").unwrap();
+
+        assert_eq!(
+            QuicErrorCode::register(1001, "griefing"),
+            Err(QuicErrorCodeError::CodeTaken {
+                code: 1001,
+                name: "cheating"
+            })
+        );
+        assert_eq!(
+            QuicErrorCode::register(1002, "cheating"),
+            Err(QuicErrorCodeError::NameTaken {
+                name: "cheating",
+                code: 1001
+            })
+        );
+        assert_eq!(
+            QuicErrorCode::register(1003, "kicked"),
+            Err(QuicErrorCodeError::NameTaken {
+                name: "kicked",
+                code: QuicErrorCode::KICKED.value()
+            })
+        );
+    }
+
+    #[test]
+    fn registering_twice_is_idempotent() {
+        let first = QuicErrorCode::register(2001, "desync").unwrap();
+        let second = QuicErrorCode::register(2001, "desync").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(QuicErrorCode::from_name("desync"), Some(first));
+        assert_eq!(first.name(), Some("desync"));
+        assert_eq!(first.to_string(), "desync (2001)");
+    }
+
+    #[test]
+    fn round_trips_through_application_errors() {
+        let code = QuicErrorCode::new(3001).unwrap();
+        let error: application::Error = code.into();
+
+        assert_eq!(QuicErrorCode::from(error), code);
+        assert_eq!(code.name(), None);
+        assert!(QuicErrorCode::KICKED.is_reserved());
+    }
+}
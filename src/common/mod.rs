@@ -5,12 +5,13 @@ use tokio::sync::mpsc::error::TrySendError;
 use crate::common::id::IdGenerator;
 
 pub mod attempt;
+pub mod config;
 pub mod connection;
+pub mod error_code;
 pub(crate) mod id;
-pub(crate) mod orchestrator;
+pub mod orchestrator;
 pub mod plugin;
 pub mod runtime;
-pub mod status_code;
 pub mod stream;
 pub(crate) mod task_state;
 
@@ -1,12 +1,151 @@
-use tokio::{runtime::Handle, task::JoinHandle};
+use bevy::log::{error, tracing};
+use futures::{FutureExt, StreamExt, stream::FuturesUnordered};
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
+use tokio::{runtime::Handle, select, sync::mpsc};
 
-pub(crate) struct AsyncOrchestrator {
+use crate::common::task_state::QuicTaskState;
+
+type OrchestratedTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Drives the async side of a connection and all of its streams on a single
+/// tokio task, rather than spawning a task per stream.
+///
+/// Cloning the orchestrator is cheap, every clone spawns onto the same task.
+/// The task quits once every clone has been dropped and all of its tasks have finished.
+///
+/// Every [QuicConnection][crate::common::connection::QuicConnection] has its own, see
+/// [orchestrator][crate::common::connection::QuicConnection::orchestrator()]. Streams created
+/// from raw s2n-quic streams should share one rather than creating one each.
+#[derive(Debug, Clone)]
+pub struct AsyncOrchestrator {
     runtime: Handle,
-    task_join: JoinHandle<()>,
+    spawner: mpsc::UnboundedSender<OrchestratedTask>,
+}
+
+impl AsyncOrchestrator {
+    pub fn new(runtime: Handle) -> Self {
+        let (spawner, receiver) = mpsc::unbounded_channel();
+
+        let task = AsyncOrchestratorTask {
+            receiver,
+            tasks: FuturesUnordered::new(),
+        };
+
+        runtime.spawn(task.run());
+
+        Self { runtime, spawner }
+    }
+
+    pub fn runtime(&self) -> &Handle {
+        &self.runtime
+    }
+
+    /// Runs the future on the orchestrator task, its output can be read
+    /// through the returned [QuicTaskState] once it has finished.
+    ///
+    /// A panic in the future only stops that future, it's returned as an error.
+    pub(crate) fn spawn<T>(
+        &self,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> QuicTaskState<T>
+    where
+        T: Clone + Send + Sync + From<Arc<dyn Error + Send + Sync>> + 'static,
+    {
+        let result = Arc::new(OnceLock::new());
+        let task_result = result.clone();
+
+        let task = async move {
+            let output = match AssertUnwindSafe(future).catch_unwind().await {
+                Ok(output) => output,
+                Err(panic) => {
+                    let err = TaskPanicked::from_payload(panic);
+                    error!("{err}");
+                    T::from(Arc::new(err))
+                }
+            };
+
+            let _ = task_result.set(output);
+        };
+
+        if self.spawner.send(Box::pin(task)).is_err() {
+            error!("Orchestrator task has quit, unable to spawn a new task.");
+            let _ = result.set(T::from(Arc::new(OrchestratorClosed)));
+        }
+
+        QuicTaskState::new(self.runtime.clone(), result)
+    }
+}
+
+struct AsyncOrchestratorTask {
+    receiver: mpsc::UnboundedReceiver<OrchestratedTask>,
+    tasks: FuturesUnordered<OrchestratedTask>,
+}
+
+impl AsyncOrchestratorTask {
+    #[tracing::instrument(name = "quic_orchestrator_task", skip_all)]
+    async fn run(mut self) {
+        loop {
+            select! {
+                task_opt = self.receiver.recv() => {
+                    let Some(task) = task_opt else {
+                        break;
+                    };
+
+                    self.tasks.push(task);
+                }
+
+                Some(()) = self.tasks.next(), if !self.tasks.is_empty() => {}
+            }
+        }
+
+        // Nothing new can be spawned, let the remaining tasks finish.
+        while self.tasks.next().await.is_some() {}
+    }
+}
+
+/// A task run by an [AsyncOrchestrator] panicked.
+#[derive(Debug)]
+pub struct TaskPanicked(String);
+
+impl TaskPanicked {
+    fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Unknown panic".to_string()
+        };
+
+        Self(message)
+    }
 }
 
-impl AsyncOrchestrator {}
+impl fmt::Display for TaskPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Async task panicked: {}", self.0)
+    }
+}
 
-struct AsyncOrchestratorTask {}
+impl Error for TaskPanicked {}
+
+/// The orchestrator task quit before a new task could be spawned on it,
+/// this usually means the runtime is shutting down.
+#[derive(Debug)]
+pub struct OrchestratorClosed;
+
+impl fmt::Display for OrchestratorClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The orchestrator task has quit.")
+    }
+}
 
-impl AsyncOrchestratorTask {}
+impl Error for OrchestratorClosed {}
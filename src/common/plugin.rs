@@ -3,21 +3,37 @@ use bevy::{
     app::{Plugin, Update},
     ecs::{
         entity::Entity,
+        hierarchy::ChildOf,
+        query::{Or, With},
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query},
     },
 };
 
-use crate::common::{
-    connection::QuicConnection,
-    stream::{receive::QuicReceiveStream, send::QuicSendStream},
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::{
+        connection::{
+            QuicConnection,
+            state::{QuicClosing, QuicConnectionState},
+        },
+        stream::{
+            lifecycle::trigger_stream_closed, receive::QuicReceiveStream,
+            send::QuicSendStream,
+        },
+    },
 };
 
 /// A plugin which handles any connection or stream components which have been disconnected.
 ///
-/// Connection disconnects will trigger aeronet's [Disconnected][aeronet_io::connection::Disconnected]
-/// event.
+/// Connections which start shutting down are moved to [QuicConnectionState::Closing] and
+/// trigger [QuicClosing]. Once closed they're moved to [QuicConnectionState::Closed] and
+/// trigger aeronet's [Disconnected][aeronet_io::connection::Disconnected] event.
 ///
-/// Streams will be disconnected without an event firing
+/// Closed streams trigger [QuicStreamFinished][crate::common::stream::lifecycle::QuicStreamFinished],
+/// [QuicStreamReset][crate::common::stream::lifecycle::QuicStreamReset] or
+/// [QuicStreamErrored][crate::common::stream::lifecycle::QuicStreamErrored] before their
+/// entity is despawned.
 pub struct DisconnectHandlerPlugin;
 
 impl Plugin for DisconnectHandlerPlugin {
@@ -26,46 +42,65 @@ impl Plugin for DisconnectHandlerPlugin {
             Update,
             (
                 handle_connection_disconnections,
-                handle_rec_stream_disconnections,
-                handle_send_stream_disconnections,
-            ),
+                handle_stream_disconnections,
+            )
+                .run_if(quic_tick_ready),
         );
     }
 }
 
 fn handle_connection_disconnections(
     mut commands: Commands,
-    query: Query<(Entity, &mut QuicConnection)>,
+    query: Query<(Entity, &mut QuicConnection, &mut QuicConnectionState)>,
 ) {
-    for (entity, mut connection) in query {
+    for (entity, mut connection, mut state) in query {
         if let Some(reason) = connection.get_disconnect_reason() {
+            *state = QuicConnectionState::Closed;
             let disconnect_reason = DisconnectReason::from(reason);
             commands.trigger(aeronet_io::connection::Disconnected {
                 entity,
                 reason: disconnect_reason,
             });
             commands.entity(entity).remove::<QuicConnection>();
+        } else if !connection.is_open() && *state != QuicConnectionState::Closing {
+            *state = QuicConnectionState::Closing;
+            commands.trigger(QuicClosing {
+                entity,
+                connection_id: connection.id(),
+            });
         }
     }
 }
 
-fn handle_rec_stream_disconnections(
-    mut commands: Commands,
-    query: Query<(Entity, &mut QuicReceiveStream)>,
-) {
-    for (entity, mut stream) in query {
-        if let Some(_reason) = stream.get_disconnect_reason() {
-            commands.entity(entity).despawn();
-        }
-    }
-}
+type StreamQueryData = (
+    Entity,
+    Option<&'static mut QuicReceiveStream>,
+    Option<&'static mut QuicSendStream>,
+    Option<&'static ChildOf>,
+);
 
-fn handle_send_stream_disconnections(
+type StreamQueryFilter = Or<(With<QuicReceiveStream>, With<QuicSendStream>)>;
+
+fn handle_stream_disconnections(
     mut commands: Commands,
-    query: Query<(Entity, &mut QuicSendStream)>,
+    query: Query<StreamQueryData, StreamQueryFilter>,
 ) {
-    for (entity, mut stream) in query {
-        if let Some(_reason) = stream.get_disconnect_reason() {
+    for (entity, rec, send, parent) in query {
+        let rec_closed = rec.and_then(|mut stream| {
+            let reason = stream.get_disconnect_reason()?;
+            Some((stream.id(), reason))
+        });
+
+        // Either half closing closes the whole stream entity
+        let closed = rec_closed.or_else(|| {
+            send.and_then(|mut stream| {
+                let reason = stream.get_disconnect_reason()?;
+                Some((stream.id(), reason))
+            })
+        });
+
+        if let Some((stream_id, reason)) = closed {
+            trigger_stream_closed(&mut commands, entity, stream_id, parent, reason);
             commands.entity(entity).despawn();
         }
     }
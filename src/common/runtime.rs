@@ -1,25 +1,160 @@
-use bevy::{
-    ecs::resource::Resource,
-    prelude::{Deref, DerefMut},
+use bevy::{ecs::resource::Resource, prelude::Deref};
+use std::{io, thread};
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    sync::oneshot,
 };
-use tokio::runtime::{Handle, Runtime};
 
-#[derive(Resource, Deref, DerefMut)]
-pub struct TokioRuntime(pub(crate) Runtime);
+pub const DEFAULT_THREAD_NAME: &str = "quic-async-worker";
+
+/// Selects the tokio runtime the async side of every endpoint, connection and stream runs on.
+///
+/// Set on [QuicAsyncPlugin][crate::async_plugin::QuicAsyncPlugin::with_runtime()],
+/// defaults to a multi-thread runtime with one worker per core.
+#[derive(Debug, Clone)]
+pub struct QuicRuntimeConfig {
+    mode: RuntimeMode,
+    worker_threads: Option<usize>,
+    thread_name: String,
+}
+
+#[derive(Debug, Clone)]
+enum RuntimeMode {
+    MultiThread,
+    CurrentThread,
+    External(Handle),
+}
+
+impl Default for QuicRuntimeConfig {
+    fn default() -> Self {
+        Self::multi_thread()
+    }
+}
+
+impl QuicRuntimeConfig {
+    /// A multi-thread runtime owned by the plugin.
+    pub fn multi_thread() -> Self {
+        Self {
+            mode: RuntimeMode::MultiThread,
+            worker_threads: None,
+            thread_name: DEFAULT_THREAD_NAME.to_string(),
+        }
+    }
+
+    /// A single threaded runtime owned by the plugin, all async work happens on
+    /// one dedicated thread. Useful for small headless tools and tests.
+    pub fn current_thread() -> Self {
+        Self {
+            mode: RuntimeMode::CurrentThread,
+            ..Self::multi_thread()
+        }
+    }
+
+    /// Runs on a runtime owned by the host application instead of creating one.
+    ///
+    /// The runtime must have IO and time enabled and has to outlive the app, the worker
+    /// count and thread name settings are ignored. A current-thread runtime only makes
+    /// progress while the host is driving it with `Runtime::block_on`.
+    pub fn from_handle(handle: Handle) -> Self {
+        Self {
+            mode: RuntimeMode::External(handle),
+            ..Self::multi_thread()
+        }
+    }
+
+    /// Sets how many worker threads a multi-thread runtime uses,
+    /// defaults to the number of cores.
+    pub fn with_worker_threads(mut self, worker_threads: usize) -> Self {
+        self.worker_threads = Some(worker_threads.max(1));
+        self
+    }
+
+    /// Sets the name of the runtime threads, defaults to [DEFAULT_THREAD_NAME].
+    pub fn with_thread_name(mut self, thread_name: impl Into<String>) -> Self {
+        self.thread_name = thread_name.into();
+        self
+    }
+
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads
+    }
+
+    pub fn thread_name(&self) -> &str {
+        &self.thread_name
+    }
+}
+
+/// The tokio runtime used by every QUIC component, dereferences to its [Handle].
+#[derive(Resource, Deref)]
+pub struct TokioRuntime {
+    #[deref]
+    handle: Handle,
+    /// Keeps a runtime owned by the plugin alive
+    _owner: RuntimeOwner,
+}
+
+enum RuntimeOwner {
+    Runtime {
+        _runtime: Runtime,
+    },
+    /// The runtime is driven by its own thread, it shuts down once this is dropped.
+    Driver {
+        _shutdown: oneshot::Sender<()>,
+    },
+    External,
+}
 
 impl Default for TokioRuntime {
     fn default() -> Self {
-        Self(
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .expect("Unable to create async runtime."),
-        )
+        Self::new(QuicRuntimeConfig::default()).expect("Unable to create async runtime.")
     }
 }
 
 impl TokioRuntime {
+    pub fn new(config: QuicRuntimeConfig) -> io::Result<Self> {
+        match config.mode {
+            RuntimeMode::MultiThread => {
+                let mut builder = Builder::new_multi_thread();
+                builder.enable_all().thread_name(config.thread_name);
+
+                if let Some(worker_threads) = config.worker_threads {
+                    builder.worker_threads(worker_threads);
+                }
+
+                let runtime = builder.build()?;
+
+                Ok(Self {
+                    handle: runtime.handle().clone(),
+                    _owner: RuntimeOwner::Runtime { _runtime: runtime },
+                })
+            }
+            RuntimeMode::CurrentThread => {
+                let runtime = Builder::new_current_thread().enable_all().build()?;
+                let handle = runtime.handle().clone();
+                let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+
+                // A current-thread runtime only runs tasks while something blocks on it.
+                thread::Builder::new()
+                    .name(config.thread_name)
+                    .spawn(move || {
+                        let _ = runtime.block_on(shutdown_receiver);
+                    })?;
+
+                Ok(Self {
+                    handle,
+                    _owner: RuntimeOwner::Driver {
+                        _shutdown: shutdown,
+                    },
+                })
+            }
+            RuntimeMode::External(handle) => Ok(Self {
+                handle,
+                _owner: RuntimeOwner::External,
+            }),
+        }
+    }
+
     pub fn handle(&self) -> &Handle {
-        self.0.handle()
+        &self.handle
     }
 }
@@ -0,0 +1,238 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        hierarchy::{ChildOf, Children},
+        query::{Has, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query},
+    },
+    log::{error, tracing},
+};
+use std::time::Duration;
+
+use crate::{
+    async_plugin::quic_tick_ready,
+    client::marker::QuicClientMarker,
+    common::{
+        connection::{
+            QuicConnection, session::QuicConnectionSession, task::PeerStreamFilter,
+        },
+        error_code::QuicErrorCode,
+        stream::{
+            QuicPeerStreamAttempt,
+            channel::{DEFAULT_CHANNEL_HEADER_TIMEOUT, QuicAwaitingChannelHeader},
+            receive::QuicReceiveStream,
+        },
+    },
+    server::marker::QuicServerMarker,
+};
+
+/// Which kinds of stream opened by the peer are accepted, see [AcceptPolicy].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AcceptedStreams {
+    /// Accepts both bidirectional and unidirectional streams.
+    #[default]
+    All,
+    Bidirectional,
+    Unidirectional,
+    /// Refuses every peer stream.
+    None,
+    /// Leaves accepting streams to your own system, which calls
+    /// [accept_stream][QuicConnection::accept_stream()] or one of its siblings.
+    Manual,
+}
+
+/// Decides which streams opened by the peer the [StreamAcceptorPlugin] accepts on a connection.
+///
+/// Every connection gets the default policy, which accepts every peer stream. Refused streams,
+/// and streams over the concurrent stream limit, are reset with the refuse code.
+///
+/// ```ignore
+/// commands.entity(connection_entity).insert(
+///     AcceptPolicy::new(AcceptedStreams::Bidirectional).with_max_concurrent_streams(8),
+/// );
+/// ```
+#[derive(Debug, Clone, Component)]
+pub struct AcceptPolicy {
+    accepted: AcceptedStreams,
+    max_concurrent_streams: Option<usize>,
+    refuse_code: QuicErrorCode,
+    channel_header: bool,
+    channel_header_timeout: Duration,
+}
+
+impl Default for AcceptPolicy {
+    fn default() -> Self {
+        Self::new(AcceptedStreams::All)
+    }
+}
+
+impl AcceptPolicy {
+    pub fn new(accepted: AcceptedStreams) -> Self {
+        Self {
+            accepted,
+            max_concurrent_streams: None,
+            refuse_code: QuicErrorCode::STREAM_REFUSED,
+            channel_header: false,
+            channel_header_timeout: DEFAULT_CHANNEL_HEADER_TIMEOUT,
+        }
+    }
+
+    /// Refuses new peer streams while the connection already has this many open,
+    /// pending accepts are counted as well.
+    pub fn with_max_concurrent_streams(mut self, max_streams: usize) -> Self {
+        self.max_concurrent_streams = Some(max_streams);
+        self
+    }
+
+    /// The code refused streams are reset with, [QuicErrorCode::STREAM_REFUSED] by default.
+    pub fn with_refuse_code(mut self, code: QuicErrorCode) -> Self {
+        self.refuse_code = code;
+        self
+    }
+
+    /// Expects every accepted stream to start with a channel header, see
+    /// [QuicChannelHeader][crate::common::stream::channel::QuicChannelHeader].
+    pub fn with_channel_header(mut self) -> Self {
+        self.channel_header = true;
+        self
+    }
+
+    /// How long a stream may take to send its channel header before it's refused,
+    /// [DEFAULT_CHANNEL_HEADER_TIMEOUT] by default.
+    pub fn with_channel_header_timeout(mut self, timeout: Duration) -> Self {
+        self.channel_header_timeout = timeout;
+        self
+    }
+
+    pub fn accepted(&self) -> AcceptedStreams {
+        self.accepted
+    }
+
+    pub fn max_concurrent_streams(&self) -> Option<usize> {
+        self.max_concurrent_streams
+    }
+
+    pub fn refuse_code(&self) -> QuicErrorCode {
+        self.refuse_code
+    }
+
+    pub fn expects_channel_header(&self) -> bool {
+        self.channel_header
+    }
+
+    pub fn channel_header_timeout(&self) -> Duration {
+        self.channel_header_timeout
+    }
+
+    /// The filter for the next accept, given how many peer streams the connection has.
+    fn filter(&self, open_streams: usize) -> PeerStreamFilter {
+        let at_capacity = self
+            .max_concurrent_streams
+            .is_some_and(|max| open_streams >= max);
+
+        let (bidirectional, unidirectional) = match self.accepted {
+            _ if at_capacity => (false, false),
+            AcceptedStreams::All => (true, true),
+            AcceptedStreams::Bidirectional => (true, false),
+            AcceptedStreams::Unidirectional => (false, true),
+            AcceptedStreams::None | AcceptedStreams::Manual => (false, false),
+        };
+
+        PeerStreamFilter {
+            bidirectional,
+            unidirectional,
+            refuse_code: self.refuse_code.into(),
+        }
+    }
+}
+
+/// This plugin accepts incoming streams on every client and server connection according
+/// to its [AcceptPolicy], spawning them as children of their [QuicConnection]s.
+#[derive(Debug)]
+pub struct StreamAcceptorPlugin;
+
+impl Plugin for StreamAcceptorPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app.add_systems(Update, accept_streams.run_if(quic_tick_ready));
+    }
+}
+
+type ConnectionQueryData = (
+    Entity,
+    &'static mut QuicConnection,
+    &'static AcceptPolicy,
+    Option<&'static Children>,
+    Has<QuicServerMarker>,
+    Has<QuicClientMarker>,
+);
+
+fn accept_streams(
+    mut commands: Commands,
+    // Sessions over the whole connection accept their own streams
+    connections: Query<ConnectionQueryData, Without<QuicConnectionSession>>,
+    streams: Query<(Option<&QuicReceiveStream>, Has<QuicPeerStreamAttempt>)>,
+) {
+    for (entity, mut connection, policy, children, is_server, is_client) in connections {
+        if policy.accepted() == AcceptedStreams::Manual
+            || !connection.should_poll_accept()
+        {
+            continue;
+        }
+
+        // Every stream opened by the peer has a receiving half
+        let open_streams = children
+            .into_iter()
+            .flatten()
+            .filter(|child| match streams.get(**child) {
+                Ok((Some(receive), _)) => !receive.id().is_local(),
+                Ok((None, is_attempt)) => is_attempt,
+                Err(_) => false,
+            })
+            .count();
+
+        handle_stream_accept(
+            &mut commands,
+            entity,
+            &mut connection,
+            policy,
+            open_streams,
+            (is_server, is_client),
+        );
+    }
+}
+
+#[tracing::instrument(name = "accept_stream", skip_all, fields(parent_id = %connection.parent_id()))]
+fn handle_stream_accept(
+    commands: &mut Commands,
+    connection_entity: Entity,
+    connection: &mut QuicConnection,
+    policy: &AcceptPolicy,
+    open_streams: usize,
+    (is_server, is_client): (bool, bool),
+) {
+    match connection.accept_filtered_stream(policy.filter(open_streams)) {
+        Ok(peer_attempt) => {
+            let mut attempt = commands.spawn((peer_attempt, ChildOf(connection_entity)));
+
+            if policy.expects_channel_header() {
+                attempt.insert(QuicAwaitingChannelHeader::new(
+                    policy.channel_header_timeout(),
+                ));
+            }
+
+            if is_server {
+                attempt.insert(QuicServerMarker);
+            }
+
+            if is_client {
+                attempt.insert(QuicClientMarker);
+            }
+        }
+        Err(err) => {
+            error!("Error accepting stream from connection: {}", err);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        bundle::Bundle,
+        change_detection::Mut,
+        component::Component,
+        entity::Entity,
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, EntityCommands, Query, Res},
+    },
+    log::{info, tracing, warn},
+};
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use thiserror::Error as ThisError;
+
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::{
+        error_code::QuicErrorCode,
+        stream::{receive::QuicReceiveStream, send::QuicSendStream},
+    },
+};
+
+/// The longest channel name which can be sent in a channel header.
+pub const MAX_CHANNEL_NAME_LEN: usize = 255;
+
+/// How long an accepted stream may take to send its channel header by default, see
+/// [AcceptPolicy::with_channel_header_timeout()][crate::common::stream::acceptor::AcceptPolicy::with_channel_header_timeout()].
+pub const DEFAULT_CHANNEL_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+type InsertChannel = dyn Fn(&mut EntityCommands) + Send + Sync;
+
+/// The channel a stream was opened for, added to both ends of the stream once the
+/// channel header has been sent or received.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct QuicStreamChannel(String);
+
+impl QuicStreamChannel {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Add this to a stream attempt opened by this endpoint to send the channel name as the first
+/// message of the stream. Once sent it's replaced with a [QuicStreamChannel] and whatever was
+/// registered for the channel.
+///
+/// The peer must expect the header, see
+/// [AcceptPolicy::with_channel_header()][crate::common::stream::acceptor::AcceptPolicy::with_channel_header()].
+/// Channel streams should only send framed messages.
+#[derive(Debug, Clone, Component)]
+pub struct QuicChannelHeader(String);
+
+impl QuicChannelHeader {
+    /// Fails if the name is longer than [MAX_CHANNEL_NAME_LEN].
+    pub fn new(name: impl Into<String>) -> Result<Self, ChannelNameTooLong> {
+        let name = name.into();
+
+        if name.len() > MAX_CHANNEL_NAME_LEN {
+            return Err(ChannelNameTooLong(name.len()));
+        }
+
+        Ok(Self(name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, ThisError)]
+#[error("Channel names can be at most {MAX_CHANNEL_NAME_LEN} bytes long, got {0}")]
+pub struct ChannelNameTooLong(usize);
+
+/// Added to streams accepted from the peer while their channel header hasn't arrived yet.
+///
+/// Nothing should be read from the stream in the meantime, the aeronet session of the stream
+/// is held back until the header has been read. Streams still waiting once the deadline has
+/// passed are refused with [QuicErrorCode::PROTOCOL_VIOLATION].
+#[derive(Debug, Clone, Copy, Component)]
+pub struct QuicAwaitingChannelHeader {
+    deadline: Instant,
+}
+
+impl QuicAwaitingChannelHeader {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+/// The channels known to this app, mapping each channel name to the components
+/// inserted on streams of that channel.
+#[derive(Default, Resource)]
+pub struct QuicChannelRegistry {
+    channels: HashMap<String, Arc<InsertChannel>>,
+}
+
+impl fmt::Debug for QuicChannelRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.channels.keys()).finish()
+    }
+}
+
+impl QuicChannelRegistry {
+    /// Inserts a clone of the bundle on every stream of the channel,
+    /// replacing any bundle registered for it before.
+    pub fn register<B: Bundle + Clone>(&mut self, name: impl Into<String>, bundle: B) {
+        self.channels.insert(
+            name.into(),
+            Arc::new(move |entity: &mut EntityCommands| {
+                entity.insert(bundle.clone());
+            }),
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.channels.contains_key(name)
+    }
+
+    fn insert(&self, entity: &mut EntityCommands, name: &str) -> bool {
+        let Some(insert) = self.channels.get(name) else {
+            return false;
+        };
+
+        insert(entity);
+        entity.insert(QuicStreamChannel(name.to_owned()));
+        true
+    }
+}
+
+/// Registers stream channels directly on the [App].
+///
+/// ```ignore
+/// #[derive(Component, Clone)]
+/// struct ChatChannel;
+///
+/// app.register_stream_channel("chat", ChatChannel);
+/// ```
+pub trait QuicChannelAppExt {
+    /// See [QuicChannelRegistry::register()].
+    fn register_stream_channel<B: Bundle + Clone>(
+        &mut self,
+        name: impl Into<String>,
+        bundle: B,
+    ) -> &mut Self;
+}
+
+impl QuicChannelAppExt for App {
+    fn register_stream_channel<B: Bundle + Clone>(
+        &mut self,
+        name: impl Into<String>,
+        bundle: B,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<QuicChannelRegistry>()
+            .register(name, bundle);
+        self
+    }
+}
+
+/// The plugin which sends and reads channel headers, inserting the components registered
+/// in the [QuicChannelRegistry] on each channel's streams.
+///
+/// Peer streams with an unknown channel, or which don't send their header in time, are
+/// refused with [QuicErrorCode::PROTOCOL_VIOLATION].
+#[derive(Debug)]
+pub struct StreamChannelPlugin;
+
+impl Plugin for StreamChannelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QuicChannelRegistry>().add_systems(
+            Update,
+            (send_channel_headers, read_channel_headers).run_if(quic_tick_ready),
+        );
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn send_channel_headers(
+    mut commands: Commands,
+    registry: Res<QuicChannelRegistry>,
+    query: Query<(Entity, &QuicChannelHeader, &mut QuicSendStream)>,
+) {
+    for (entity, header, mut send) in query {
+        let name = header.name();
+
+        if let Err(e) = send.send_message(Bytes::copy_from_slice(name.as_bytes())) {
+            warn!(
+                "Unable to send channel header \"{}\" on stream {}: {}",
+                name,
+                send.id(),
+                e
+            );
+            continue;
+        }
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<QuicChannelHeader>();
+
+        if !registry.insert(&mut entity, name) {
+            warn!(
+                "Opened stream {} for unregistered channel \"{}\"",
+                send.id(),
+                name
+            );
+            entity.insert(QuicStreamChannel(name.to_owned()));
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn read_channel_headers(
+    mut commands: Commands,
+    registry: Res<QuicChannelRegistry>,
+    query: Query<(
+        Entity,
+        &QuicAwaitingChannelHeader,
+        &mut QuicReceiveStream,
+        Option<&mut QuicSendStream>,
+    )>,
+) {
+    let mut header = Vec::with_capacity(1);
+    let now = Instant::now();
+
+    for (entity, awaiting, mut rec, send) in query {
+        header.clear();
+
+        if rec.recv_messages(&mut header, 1) == 0 {
+            if now >= awaiting.deadline() {
+                warn!(
+                    "Refusing stream {} which sent no channel header in time",
+                    rec.id()
+                );
+
+                commands
+                    .entity(entity)
+                    .remove::<QuicAwaitingChannelHeader>();
+                refuse_stream(&mut rec, send);
+            }
+
+            continue;
+        }
+
+        let stream_id = rec.id();
+        let name = std::str::from_utf8(&header[0].payload).ok();
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<QuicAwaitingChannelHeader>();
+
+        if let Some(name) = name
+            && registry.insert(&mut entity, name)
+        {
+            info!("Stream {} opened for channel \"{}\"", stream_id, name);
+            continue;
+        }
+
+        warn!(
+            "Refusing stream {} with an unknown channel header",
+            stream_id
+        );
+
+        refuse_stream(&mut rec, send);
+    }
+}
+
+fn refuse_stream(rec: &mut QuicReceiveStream, send: Option<Mut<QuicSendStream>>) {
+    let _ = rec.stop_send(QuicErrorCode::PROTOCOL_VIOLATION);
+
+    if let Some(mut send) = send {
+        let _ = send.reset(QuicErrorCode::PROTOCOL_VIOLATION);
+    }
+}
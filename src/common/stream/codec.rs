@@ -0,0 +1,141 @@
+use bytes::Bytes;
+use serde::{Serialize, de::DeserializeOwned};
+use std::error::Error;
+use thiserror::Error;
+
+use crate::common::stream::{framing::SendMessageError, id::StreamId};
+
+/// Turns Rust types into message payloads and back for
+/// [send_typed][crate::common::stream::send::QuicSendStream::send_typed()] and
+/// [recv_typed][crate::common::stream::receive::QuicReceiveStream::recv_typed()].
+///
+/// Both ends of a stream must use the same codec. Each built in codec is behind
+/// the cargo feature of the same name, implement this trait to use any other format.
+pub trait MessageCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Bytes, Box<dyn Error + Send + Sync>>;
+
+    fn decode<T: DeserializeOwned>(
+        data: &[u8],
+    ) -> Result<T, Box<dyn Error + Send + Sync>>;
+}
+
+/// Encodes messages with [bincode](https://docs.rs/bincode) using its standard configuration.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl MessageCodec for BincodeCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Bytes, Box<dyn Error + Send + Sync>> {
+        let data = bincode::serde::encode_to_vec(value, bincode::config::standard())?;
+        Ok(data.into())
+    }
+
+    fn decode<T: DeserializeOwned>(
+        data: &[u8],
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        let (value, _) =
+            bincode::serde::decode_from_slice(data, bincode::config::standard())?;
+        Ok(value)
+    }
+}
+
+/// Encodes messages with [postcard](https://docs.rs/postcard).
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardCodec;
+
+#[cfg(feature = "postcard")]
+impl MessageCodec for PostcardCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Bytes, Box<dyn Error + Send + Sync>> {
+        Ok(postcard::to_allocvec(value)?.into())
+    }
+
+    fn decode<T: DeserializeOwned>(
+        data: &[u8],
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        Ok(postcard::from_bytes(data)?)
+    }
+}
+
+/// Encodes messages as JSON with [serde_json](https://docs.rs/serde_json),
+/// useful when debugging traffic.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl MessageCodec for JsonCodec {
+    fn encode<T: Serialize>(value: &T) -> Result<Bytes, Box<dyn Error + Send + Sync>> {
+        Ok(serde_json::to_vec(value)?.into())
+    }
+
+    fn decode<T: DeserializeOwned>(
+        data: &[u8],
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// Errors which can occur when sending a typed message.
+#[derive(Debug, Error)]
+pub enum TypedSendError {
+    #[error("Unable to encode message: {0}")]
+    Encode(Box<dyn Error + Send + Sync>),
+    #[error(transparent)]
+    Send(#[from] SendMessageError),
+}
+
+/// A message received on a stream which couldn't be decoded into the expected type.
+///
+/// The stream itself is left open, later messages can still be decoded.
+#[derive(Debug, Error)]
+#[error("Unable to decode message on stream {stream_id}: {source}")]
+pub struct QuicDecodeError {
+    pub stream_id: StreamId,
+    /// The raw payload of the message.
+    pub payload: Bytes,
+    pub source: Box<dyn Error + Send + Sync>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Message = (u32, String, Vec<u8>);
+
+    fn message() -> Message {
+        (7, "hello".to_string(), vec![1, 2, 3])
+    }
+
+    fn round_trip<C: MessageCodec>() {
+        let encoded = C::encode(&message()).unwrap();
+        assert_eq!(C::decode::<Message>(&encoded).unwrap(), message());
+    }
+
+    fn refuses_garbage<C: MessageCodec>() {
+        assert!(C::decode::<Message>(&[0xff]).is_err());
+        assert!(C::decode::<Message>(&[]).is_err());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trips() {
+        round_trip::<BincodeCodec>();
+        refuses_garbage::<BincodeCodec>();
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn postcard_round_trips() {
+        round_trip::<PostcardCodec>();
+        refuses_garbage::<PostcardCodec>();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trips() {
+        round_trip::<JsonCodec>();
+        refuses_garbage::<JsonCodec>();
+    }
+}
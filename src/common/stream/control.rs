@@ -0,0 +1,208 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::sync::{Notify, mpsc::error::TrySendError};
+
+type TaskError = Box<dyn Error + Send + Sync>;
+
+/// Creates the control link between a stream component and its async task.
+///
+/// Commands go to the task and errors come back from it through a single shared
+/// allocation, rather than a control and an error channel per stream. Each queue
+/// refuses new entries once it holds its capacity.
+pub(crate) fn control_link<C>(
+    command_capacity: usize,
+    error_capacity: usize,
+) -> (StreamControl<C>, StreamControlReceiver<C>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            commands: VecDeque::new(),
+            errors: VecDeque::new(),
+            command_capacity,
+            error_capacity,
+            closed: false,
+        }),
+        notify: Notify::new(),
+    });
+
+    (StreamControl(shared.clone()), StreamControlReceiver(shared))
+}
+
+struct Shared<C> {
+    state: Mutex<State<C>>,
+    /// Wakes the task when a command is queued or the component is dropped.
+    notify: Notify,
+}
+
+impl<C> Shared<C> {
+    fn lock(&self) -> MutexGuard<'_, State<C>> {
+        // Nothing panics while the lock is held, the state is always consistent
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.notify.notify_one();
+    }
+}
+
+struct State<C> {
+    commands: VecDeque<C>,
+    errors: VecDeque<TaskError>,
+    command_capacity: usize,
+    error_capacity: usize,
+    /// Set once either end has been dropped.
+    closed: bool,
+}
+
+/// The sync side of a [control_link()], owned by the stream component.
+pub(crate) struct StreamControl<C>(Arc<Shared<C>>);
+
+impl<C> StreamControl<C> {
+    /// Queues a command for the task without blocking.
+    pub fn try_send(&self, command: C) -> Result<(), TrySendError<C>> {
+        let mut state = self.0.lock();
+
+        if state.closed {
+            return Err(TrySendError::Closed(command));
+        }
+
+        if state.commands.len() >= state.command_capacity {
+            return Err(TrySendError::Full(command));
+        }
+
+        state.commands.push_back(command);
+        drop(state);
+
+        self.0.notify.notify_one();
+        Ok(())
+    }
+
+    /// Returns `true` once the task has quit.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+
+    /// Takes the oldest error reported by the task.
+    pub fn pop_error(&self) -> Option<TaskError> {
+        self.0.lock().errors.pop_front()
+    }
+}
+
+impl<C> Drop for StreamControl<C> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+impl<C> fmt::Debug for StreamControl<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0.lock();
+
+        f.debug_struct("StreamControl")
+            .field("queued_commands", &state.commands.len())
+            .field("queued_errors", &state.errors.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+/// The async side of a [control_link()], owned by the stream task.
+pub(crate) struct StreamControlReceiver<C>(Arc<Shared<C>>);
+
+impl<C> StreamControlReceiver<C> {
+    /// Waits for the next command, returns `None` once the component has been
+    /// dropped and every queued command has been handled.
+    ///
+    /// Cancel safe, a command is only taken out of the queue when it's returned.
+    pub async fn recv(&mut self) -> Option<C> {
+        loop {
+            {
+                let mut state = self.0.lock();
+
+                if let Some(command) = state.commands.pop_front() {
+                    return Some(command);
+                }
+
+                if state.closed {
+                    return None;
+                }
+            }
+
+            // A notification sent in between checking and waiting is kept as a permit
+            self.0.notify.notified().await;
+        }
+    }
+
+    /// Reports an error to the component, refused if the error queue is full.
+    pub fn try_send_error(
+        &self,
+        error: TaskError,
+    ) -> Result<(), TrySendError<TaskError>> {
+        let mut state = self.0.lock();
+
+        if state.errors.len() >= state.error_capacity {
+            return Err(TrySendError::Full(error));
+        }
+
+        state.errors.push_back(error);
+        Ok(())
+    }
+}
+
+impl<C> Drop for StreamControlReceiver<C> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_commands_over_capacity() {
+        let (control, _receiver) = control_link::<u8>(2, 1);
+
+        assert!(control.try_send(1).is_ok());
+        assert!(control.try_send(2).is_ok());
+        assert!(matches!(control.try_send(3), Err(TrySendError::Full(3))));
+    }
+
+    #[test]
+    fn closes_once_either_end_is_dropped() {
+        let (control, receiver) = control_link::<u8>(2, 1);
+        drop(receiver);
+
+        assert!(control.is_closed());
+        assert!(matches!(control.try_send(1), Err(TrySendError::Closed(1))));
+
+        let (control, mut receiver) = control_link::<u8>(2, 1);
+        control.try_send(1).unwrap();
+        drop(control);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        // Commands queued before the drop are still handed out
+        assert_eq!(runtime.block_on(receiver.recv()), Some(1));
+        assert_eq!(runtime.block_on(receiver.recv()), None);
+    }
+
+    #[test]
+    fn hands_errors_back_in_order() {
+        let (control, receiver) = control_link::<u8>(1, 2);
+
+        receiver.try_send_error("first".into()).unwrap();
+        receiver.try_send_error("second".into()).unwrap();
+        assert!(receiver.try_send_error("third".into()).is_err());
+
+        assert_eq!(control.pop_error().unwrap().to_string(), "first");
+        assert_eq!(control.pop_error().unwrap().to_string(), "second");
+        assert!(control.pop_error().is_none());
+    }
+}
@@ -1,12 +1,18 @@
 use std::{error::Error, sync::Arc};
 
 use aeronet_io::{anyhow::anyhow, connection::DisconnectReason};
+use s2n_quic::stream::Error as StreamError;
+
+use crate::common::error_code::QuicErrorCode;
 
 #[derive(Clone, Debug)]
 pub enum StreamDisconnectReason {
     UserClosed,
     PeerClosed,
-    Reset(s2n_quic::application::Error),
+    /// The stream was reset by the peer with an application error code
+    Reset(QuicErrorCode),
+    /// The stream was reset locally with [reset][crate::common::stream::send::QuicSendStream::reset()]
+    UserReset(QuicErrorCode),
     InvalidStream,
     ConnectionError(s2n_quic::connection::Error),
     ResourceError,
@@ -18,6 +24,33 @@ pub enum StreamDisconnectReason {
     NoReason,
 }
 
+impl StreamDisconnectReason {
+    /// The reason a stream error ends the stream with, if it ends the stream at all.
+    /// Resets keep the application error code the peer sent.
+    pub(crate) fn from_stream_error(error: &StreamError) -> Option<Self> {
+        match error {
+            StreamError::StreamReset { error, .. } => Some(Self::Reset((*error).into())),
+            StreamError::ConnectionError { error, .. } => Some(Self::ConnectionError(*error)),
+            StreamError::InvalidStream { .. } | StreamError::SendAfterFinish { .. } => {
+                Some(Self::InvalidStream)
+            }
+            _ => None,
+        }
+    }
+
+    /// The application error code the stream, or its connection, was closed with. Registered
+    /// codes can be turned back into their name with [QuicErrorCode::name()].
+    pub fn error_code(&self) -> Option<QuicErrorCode> {
+        match self {
+            Self::Reset(code) | Self::UserReset(code) => Some(*code),
+            Self::ConnectionError(s2n_quic::connection::Error::Application { error, .. }) => {
+                Some((*error).into())
+            }
+            _ => None,
+        }
+    }
+}
+
 impl From<Arc<dyn Error + Send + Sync>> for StreamDisconnectReason {
     fn from(error: Arc<dyn Error + Send + Sync>) -> Self {
         StreamDisconnectReason::InternalError(error)
@@ -36,6 +69,9 @@ impl From<StreamDisconnectReason> for DisconnectReason {
             StreamDisconnectReason::Reset(error) => {
                 DisconnectReason::ByError(anyhow!("Stream closed by reset with code: {error}"))
             }
+            StreamDisconnectReason::UserReset(error) => {
+                DisconnectReason::ByUser(format!("Stream reset by self with code: {error}"))
+            }
             StreamDisconnectReason::InvalidStream => {
                 DisconnectReason::ByError(anyhow!("Stream is no longer valid"))
             }
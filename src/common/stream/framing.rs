@@ -0,0 +1,207 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+/// The largest message which can be sent or received with the framed stream methods
+/// unless configured otherwise, see
+/// [QuicReceiveStream::set_max_message_size][crate::common::stream::receive::QuicReceiveStream::set_max_message_size()].
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// The largest length which fits in a QUIC variable length integer.
+const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// Errors which can occur when sending or receiving length prefixed messages.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum FramingError {
+    /// The message is larger than the max message size of the stream.
+    #[error("Message of {size} bytes exceeds the max message size of {max} bytes")]
+    MessageTooLarge { size: u64, max: usize },
+}
+
+/// Encodes the length prefix of a message as a QUIC variable length integer.
+pub(crate) fn encode_header(len: usize) -> Bytes {
+    let len = len as u64;
+    debug_assert!(len <= MAX_VARINT);
+
+    let mut header = BytesMut::with_capacity(8);
+
+    if len < 1 << 6 {
+        header.put_u8(len as u8);
+    } else if len < 1 << 14 {
+        header.put_u16(len as u16 | 0x4000);
+    } else if len < 1 << 30 {
+        header.put_u32(len as u32 | 0x8000_0000);
+    } else {
+        header.put_u64(len | 0xc000_0000_0000_0000);
+    }
+
+    header.freeze()
+}
+
+/// Tries to decode a length prefix from the front of the buffer, returning
+/// the length and the size of the prefix itself.
+pub(crate) fn decode_header(buffer: &[u8]) -> Option<(u64, usize)> {
+    let first = *buffer.first()?;
+    let size = 1 << (first >> 6);
+
+    if buffer.len() < size {
+        return None;
+    }
+
+    let mut len = (first & 0x3f) as u64;
+
+    for byte in &buffer[1..size] {
+        len = (len << 8) | *byte as u64;
+    }
+
+    Some((len, size))
+}
+
+/// Reassembles length prefixed messages from the chunks of a receive stream.
+#[derive(Debug)]
+pub(crate) struct FrameDecoder {
+    buffer: BytesMut,
+    max_message_size: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl FrameDecoder {
+    pub(crate) fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    pub(crate) fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Pops the next complete message, returns `Ok(None)` if more data is needed.
+    pub(crate) fn next_message(&mut self) -> Result<Option<Bytes>, FramingError> {
+        let Some((len, header_size)) = decode_header(&self.buffer) else {
+            return Ok(None);
+        };
+
+        if len > self.max_message_size as u64 {
+            return Err(FramingError::MessageTooLarge {
+                size: len,
+                max: self.max_message_size,
+            });
+        }
+
+        let len = len as usize;
+
+        if self.buffer.len() < header_size + len {
+            self.buffer.reserve(header_size + len - self.buffer.len());
+            return Ok(None);
+        }
+
+        self.buffer.advance(header_size);
+        Ok(Some(self.buffer.split_to(len).freeze()))
+    }
+}
+
+/// Errors which can occur when sending a length prefixed message.
+#[derive(Debug, Error)]
+pub enum SendMessageError {
+    #[error(transparent)]
+    Framing(#[from] FramingError),
+    /// The outbound channel doesn't have room for the message, the message is returned.
+    #[error("The outbound channel of the stream is full.")]
+    Full(Bytes),
+    /// The async task for the stream has quit, the message is returned.
+    #[error("The outbound channel of the stream is closed.")]
+    Closed(Bytes),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: &[u8]) -> Vec<u8> {
+        let mut framed = encode_header(message.len()).to_vec();
+        framed.extend_from_slice(message);
+        framed
+    }
+
+    #[test]
+    fn headers_use_the_smallest_varint() {
+        for (len, size) in [
+            (0, 1),
+            (63, 1),
+            (64, 2),
+            ((1 << 14) - 1, 2),
+            (1 << 14, 4),
+            ((1 << 30) - 1, 4),
+            (1 << 30, 8),
+            (MAX_VARINT as usize, 8),
+        ] {
+            let header = encode_header(len);
+
+            assert_eq!(header.len(), size, "header size of {len}");
+            assert_eq!(decode_header(&header), Some((len as u64, size)));
+        }
+    }
+
+    #[test]
+    fn round_trips_messages_of_every_header_size() {
+        let messages: Vec<Vec<u8>> = [0, 1, 63, 64, 16383, 16384, 70000]
+            .into_iter()
+            .map(|len| (0..len).map(|i| i as u8).collect())
+            .collect();
+
+        let mut decoder = FrameDecoder::default();
+
+        for message in &messages {
+            decoder.push(&frame(message));
+        }
+
+        for message in &messages {
+            assert_eq!(
+                decoder.next_message().unwrap().as_deref(),
+                Some(&message[..])
+            );
+        }
+
+        assert_eq!(decoder.next_message(), Ok(None));
+    }
+
+    #[test]
+    fn reassembles_messages_split_across_chunks() {
+        let message = vec![7; 300];
+        let framed = frame(&message);
+        let mut decoder = FrameDecoder::default();
+
+        // Splits the two byte header as well
+        for chunk in framed.chunks(1).take(1).chain(framed[1..].chunks(100)) {
+            assert_eq!(decoder.next_message(), Ok(None));
+            decoder.push(chunk);
+        }
+
+        assert_eq!(
+            decoder.next_message().unwrap().as_deref(),
+            Some(&message[..])
+        );
+    }
+
+    #[test]
+    fn refuses_messages_over_the_max_size() {
+        let mut decoder = FrameDecoder::default();
+        decoder.set_max_message_size(16);
+        decoder.push(&encode_header(17));
+
+        assert_eq!(
+            decoder.next_message(),
+            Err(FramingError::MessageTooLarge { size: 17, max: 16 })
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::common::QuicParentId;
+use crate::common::{QuicParentId, QuicParentType};
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub struct StreamId {
@@ -32,4 +32,16 @@ impl StreamId {
     pub fn id(&self) -> u64 {
         self.id
     }
+
+    /// Returns `true` if the stream was opened by this endpoint rather than by the peer.
+    pub fn is_local(&self) -> bool {
+        // The lowest bit of a QUIC stream ID is set for server initiated streams
+        let client_initiated = self.id & 0b01 == 0;
+        client_initiated == (self.parent_id.connection_type() == QuicParentType::Client)
+    }
+
+    /// Returns `true` if the stream can be both sent and received on.
+    pub fn is_bidirectional(&self) -> bool {
+        self.id & 0b10 == 0
+    }
 }
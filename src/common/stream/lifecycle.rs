@@ -0,0 +1,122 @@
+use crate::common::{
+    error_code::QuicErrorCode,
+    stream::{disconnect::StreamDisconnectReason, id::StreamId},
+};
+use bevy::ecs::{
+    entity::Entity, event::EntityEvent, hierarchy::ChildOf, system::Commands,
+};
+
+/// Triggered on a stream entity once a stream opened by this endpoint is ready to use.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicStreamOpened {
+    pub entity: Entity,
+    pub stream_id: StreamId,
+    /// The connection entity, if the stream entity is one of its children.
+    pub connection: Option<Entity>,
+}
+
+/// Triggered on a stream entity once a stream opened by the peer has been accepted.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicStreamAccepted {
+    pub entity: Entity,
+    pub stream_id: StreamId,
+    /// The connection entity, if the stream entity is one of its children.
+    pub connection: Option<Entity>,
+}
+
+/// Triggered on a stream entity when the stream was closed gracefully by either side,
+/// right before the entity is despawned.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicStreamFinished {
+    pub entity: Entity,
+    pub stream_id: StreamId,
+    /// The connection entity, if the stream entity is one of its children.
+    pub connection: Option<Entity>,
+    pub reason: StreamDisconnectReason,
+}
+
+/// Triggered on a stream entity when the stream was reset with an error code by either
+/// side, right before the entity is despawned.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicStreamReset {
+    pub entity: Entity,
+    pub stream_id: StreamId,
+    /// The connection entity, if the stream entity is one of its children.
+    pub connection: Option<Entity>,
+    pub code: QuicErrorCode,
+    pub reason: StreamDisconnectReason,
+}
+
+/// Triggered on a stream entity when the stream was closed by an error,
+/// e.g. the connection closing, right before the entity is despawned.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicStreamErrored {
+    pub entity: Entity,
+    pub stream_id: StreamId,
+    /// The connection entity, if the stream entity is one of its children.
+    pub connection: Option<Entity>,
+    pub reason: StreamDisconnectReason,
+}
+
+/// Triggers [QuicStreamOpened] or [QuicStreamAccepted] depending on who opened the stream.
+pub(crate) fn trigger_stream_started(
+    commands: &mut Commands,
+    entity: Entity,
+    stream_id: StreamId,
+    parent: Option<&ChildOf>,
+) {
+    let connection = parent.map(ChildOf::parent);
+
+    if stream_id.is_local() {
+        commands.trigger(QuicStreamOpened {
+            entity,
+            stream_id,
+            connection,
+        });
+    } else {
+        commands.trigger(QuicStreamAccepted {
+            entity,
+            stream_id,
+            connection,
+        });
+    }
+}
+
+/// Triggers the event matching how the stream was closed.
+pub(crate) fn trigger_stream_closed(
+    commands: &mut Commands,
+    entity: Entity,
+    stream_id: StreamId,
+    parent: Option<&ChildOf>,
+    reason: StreamDisconnectReason,
+) {
+    let connection = parent.map(ChildOf::parent);
+
+    match reason {
+        StreamDisconnectReason::UserClosed | StreamDisconnectReason::PeerClosed => {
+            commands.trigger(QuicStreamFinished {
+                entity,
+                stream_id,
+                connection,
+                reason,
+            });
+        }
+        StreamDisconnectReason::Reset(code) | StreamDisconnectReason::UserReset(code) => {
+            commands.trigger(QuicStreamReset {
+                entity,
+                stream_id,
+                connection,
+                code,
+                reason,
+            });
+        }
+        _ => {
+            commands.trigger(QuicStreamErrored {
+                entity,
+                stream_id,
+                connection,
+                reason,
+            });
+        }
+    }
+}
@@ -3,17 +3,30 @@ use bevy::{
     ecs::component::Component,
     prelude::{Deref, DerefMut},
 };
+use bytes::Bytes;
 use s2n_quic::stream::PeerStream;
-use tokio::runtime::Handle;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc::error::TrySendError, oneshot},
+};
 
 use crate::common::{
     QuicParentId,
-    attempt::{QuicActionAttempt, TaskResult},
-    stream::{receive::QuicReceiveStream, send::QuicSendStream},
+    attempt::{QuicActionAttempt, TaskError, TaskResult},
+    orchestrator::AsyncOrchestrator,
+    stream::{control::StreamControl, receive::QuicReceiveStream, send::QuicSendStream},
 };
 
+pub mod acceptor;
+pub mod channel;
+#[cfg(feature = "serde")]
+pub mod codec;
+pub(crate) mod control;
 pub mod disconnect;
+pub mod framing;
 pub mod id;
+pub mod lifecycle;
 pub mod plugin;
 pub mod receive;
 pub mod send;
@@ -43,6 +56,13 @@ impl QuicReceiveStreamAttempt {
     ) -> Self {
         Self(QuicActionAttempt::new(handle, task, parent_id))
     }
+
+    /// Fails the attempt with [QuicActionError::TimedOut][crate::common::attempt::QuicActionError::TimedOut]
+    /// if no stream has been opened within the given duration.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
 }
 
 /// This is a structure which represents an in progress send stream.
@@ -68,6 +88,13 @@ impl QuicSendStreamAttempt {
     ) -> Self {
         Self(QuicActionAttempt::new(handle, task, parent_id))
     }
+
+    /// Fails the attempt with [QuicActionError::TimedOut][crate::common::attempt::QuicActionError::TimedOut]
+    /// if no stream has been opened within the given duration.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
 }
 
 /// This is a structure which represents an in progress bidirectional stream.
@@ -99,6 +126,13 @@ impl QuicBidirectionalStreamAttempt {
     ) -> Self {
         Self(QuicActionAttempt::new(handle, task, parent_id))
     }
+
+    /// Fails the attempt with [QuicActionError::TimedOut][crate::common::attempt::QuicActionError::TimedOut]
+    /// if no stream has been opened within the given duration.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
 }
 
 /// This is a structure which represents an in progress peer stream.
@@ -125,6 +159,13 @@ impl QuicPeerStreamAttempt {
     ) -> Self {
         Self(QuicActionAttempt::new(handle, task, parent_id))
     }
+
+    /// Fails the attempt with [QuicActionError::TimedOut][crate::common::attempt::QuicActionError::TimedOut]
+    /// if no stream has been opened within the given duration.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
 }
 
 pub enum QuicPeerStream {
@@ -133,24 +174,76 @@ pub enum QuicPeerStream {
 }
 
 impl QuicPeerStream {
+    /// Runs the async side of the stream on the orchestrator, see [QuicReceiveStream::new()].
     pub fn new(
-        runtime: Handle,
+        orchestrator: &AsyncOrchestrator,
         peer_stream: PeerStream,
         parent_id: QuicParentId,
+    ) -> Self {
+        Self::with_received(orchestrator, peer_stream, parent_id, None)
+    }
+
+    /// `received` is data already read from a receive stream, see
+    /// [QuicReceiveStream::with_received()].
+    pub(crate) fn with_received(
+        orchestrator: &AsyncOrchestrator,
+        peer_stream: PeerStream,
+        parent_id: QuicParentId,
+        received: Option<Bytes>,
     ) -> Self {
         match peer_stream {
             PeerStream::Bidirectional(bidirectional_stream) => {
                 let (rec, send) = bidirectional_stream.split();
-                let quic_rec = QuicReceiveStream::new(runtime.clone(), rec, parent_id);
-                let quic_send = QuicSendStream::new(runtime, send, parent_id);
+                let quic_rec = QuicReceiveStream::new(orchestrator, rec, parent_id);
+                let quic_send = QuicSendStream::new(orchestrator, send, parent_id);
 
                 QuicPeerStream::Bidirectional(quic_rec, quic_send)
             }
             PeerStream::Receive(rec) => {
-                let quic_rec = QuicReceiveStream::new(runtime.clone(), rec, parent_id);
+                let quic_rec = QuicReceiveStream::with_received(
+                    orchestrator,
+                    rec,
+                    parent_id,
+                    received,
+                );
 
                 QuicPeerStream::Receive(quic_rec)
             }
         }
     }
 }
+
+/// A handle to a command sent to the async task of a stream, such as
+/// [QuicSendStream::flush()]. Poll it with [attempt_result][QuicActionAttempt::attempt_result()]
+/// to find out when the command has completed.
+///
+/// Dropping the handle doesn't undo the command, only its result is lost.
+pub type QuicStreamCommand = QuicActionAttempt<()>;
+
+/// The sending half of a [QuicStreamCommand], completed by the stream task.
+pub(crate) type CommandResponder = oneshot::Sender<Result<(), TaskError>>;
+
+/// Queues a command for a stream task without blocking.
+/// If the control queue is full or closed the returned command has already failed.
+pub(crate) fn send_command<M>(
+    runtime: &Handle,
+    control: &StreamControl<M>,
+    parent_id: QuicParentId,
+    command: impl FnOnce(CommandResponder) -> M,
+) -> QuicStreamCommand {
+    let (responder, result) = oneshot::channel();
+
+    let Err(e) = control.try_send(command(responder)) else {
+        return QuicActionAttempt::new(runtime.clone(), result, parent_id);
+    };
+
+    let err = match e {
+        TrySendError::Full(_) => TrySendError::Full(()),
+        TrySendError::Closed(_) => TrySendError::Closed(()),
+    };
+
+    let (responder, result) = oneshot::channel();
+    let _ = responder.send(Err(TaskError::TaskFailed(Arc::new(err))));
+
+    QuicActionAttempt::new(runtime.clone(), result, parent_id)
+}
@@ -1,210 +1,224 @@
 use bevy::{
     app::{Plugin, Update},
     ecs::{
+        component::{Component, Mutable},
         entity::Entity,
-        system::{Commands, Query},
+        hierarchy::ChildOf,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, EntityCommands, Query},
     },
     log::{error, info, tracing},
 };
 
-use crate::common::{
-    attempt::QuicActionError,
-    stream::{
-        QuicBidirectionalStreamAttempt, QuicPeerStream, QuicPeerStreamAttempt,
-        QuicReceiveStreamAttempt, session::QuicSession,
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::{
+        QuicParentId,
+        attempt::{QuicActionAttempt, QuicActionError},
+        stream::{
+            QuicBidirectionalStreamAttempt, QuicPeerStream, QuicPeerStreamAttempt,
+            QuicReceiveStreamAttempt, QuicSendStreamAttempt, id::StreamId,
+            lifecycle::trigger_stream_started, receive::QuicReceiveStream,
+            send::QuicSendStream, session::QuicSession,
+        },
     },
 };
 
 #[derive(Debug)]
 pub struct StreamAttemptPlugin;
 
-// TODO: create systems for send/receive versions
+/// Replaces finished stream attempts with their streams, triggering
+/// [QuicStreamOpened][crate::common::stream::lifecycle::QuicStreamOpened] or
+/// [QuicStreamAccepted][crate::common::stream::lifecycle::QuicStreamAccepted] on the entity.
 impl Plugin for StreamAttemptPlugin {
     fn build(&self, app: &mut bevy::app::App) {
-        app.add_systems(Update, handle_bidir_stream_attempt)
-            .add_systems(Update, handle_rec_stream_attempt)
-            .add_systems(Update, handle_peer_stream_attempt);
+        app.add_systems(
+            Update,
+            (
+                handle_stream_attempts::<QuicBidirectionalStreamAttempt>,
+                handle_stream_attempts::<QuicReceiveStreamAttempt>,
+                handle_stream_attempts::<QuicSendStreamAttempt>,
+                handle_stream_attempts::<QuicPeerStreamAttempt>,
+            )
+                .run_if(quic_tick_ready),
+        );
     }
 }
 
-#[tracing::instrument(skip_all)]
-fn handle_bidir_stream_attempt(
-    mut commands: Commands,
-    query: Query<(Entity, &mut QuicBidirectionalStreamAttempt)>,
-) {
-    for entity_bundle in query {
-        let (entity, mut attempt) = entity_bundle;
-        let parent_id = attempt.parent_id();
+/// A stream attempt component which is replaced with its stream once it has finished.
+trait StreamAttempt: Component<Mutability = Mutable> {
+    type Stream;
 
-        let res = attempt.attempt_result();
+    fn attempt(&mut self) -> &mut QuicActionAttempt<Option<Self::Stream>>;
 
-        if let Err(e) = res {
-            match &e {
-                QuicActionError::Pending => continue,
-                QuicActionError::Consumed => {
-                    error!("Stream attempt consumed for entity: {:?}", entity)
-                }
-                QuicActionError::ConnectionFailed(error) => {
-                    error!("Stream attempt failed: {:?}", error)
-                }
-                QuicActionError::Crashed(join_error) => {
-                    error!("Stream attempt crashed: {:?}", join_error)
-                }
-            }
+    /// Inserts the components of the stream, returning its id.
+    fn insert_stream(
+        entity: &mut EntityCommands,
+        stream: Self::Stream,
+        parent_id: QuicParentId,
+    ) -> StreamId;
+}
 
-            let mut error_entity = commands.entity(entity);
+impl StreamAttempt for QuicBidirectionalStreamAttempt {
+    type Stream = (QuicReceiveStream, QuicSendStream);
 
-            #[cfg(feature = "stream-errors")]
-            {
-                use {crate::common::attempt::QuicActionErrorComponent, std::time::SystemTime};
+    fn attempt(&mut self) -> &mut QuicActionAttempt<Option<Self::Stream>> {
+        self
+    }
 
-                let err_comp = QuicActionErrorComponent::new(e, SystemTime::now());
-                let err_bundle = (err_comp, *parent_id);
+    fn insert_stream(
+        entity: &mut EntityCommands,
+        (rec, send): Self::Stream,
+        parent_id: QuicParentId,
+    ) -> StreamId {
+        info!("Spawning bidirectional stream with {parent_id}");
 
-                error_entity.insert(err_bundle);
-            }
+        let stream_id = rec.id();
+        entity.insert((rec, send, QuicSession));
+        stream_id
+    }
+}
 
-            error_entity.remove::<QuicBidirectionalStreamAttempt>();
+impl StreamAttempt for QuicReceiveStreamAttempt {
+    type Stream = QuicReceiveStream;
 
-            continue;
-        }
+    fn attempt(&mut self) -> &mut QuicActionAttempt<Option<Self::Stream>> {
+        self
+    }
 
-        if let Some((rec, send)) = res.unwrap() {
-            info!("Spawning bidirectional stream with {parent_id}");
+    fn insert_stream(
+        entity: &mut EntityCommands,
+        rec: Self::Stream,
+        parent_id: QuicParentId,
+    ) -> StreamId {
+        info!("Spawning receive stream with {parent_id}");
 
-            commands
-                .entity(entity)
-                .remove::<QuicBidirectionalStreamAttempt>()
-                .insert((rec, send, QuicSession));
-        }
-        // No new streams, delete attempt
-        else {
-            info!("No pending incoming streams, deleting attempt.");
-            commands.entity(entity).despawn();
-        }
+        let stream_id = rec.id();
+        entity.insert((rec, QuicSession));
+        stream_id
     }
 }
 
-#[tracing::instrument(skip_all)]
-fn handle_rec_stream_attempt(
-    mut commands: Commands,
-    query: Query<(Entity, &mut QuicReceiveStreamAttempt)>,
-) {
-    for entity_bundle in query {
-        let (entity, mut attempt) = entity_bundle;
-        let parent_id = attempt.parent_id();
+impl StreamAttempt for QuicSendStreamAttempt {
+    type Stream = QuicSendStream;
 
-        let res = attempt.attempt_result();
+    fn attempt(&mut self) -> &mut QuicActionAttempt<Option<Self::Stream>> {
+        self
+    }
 
-        // TODOL: make helper function with shmancy tracing span
-        if let Err(e) = res {
-            match &e {
-                QuicActionError::Pending => continue,
-                QuicActionError::Consumed => {
-                    error!("Stream attempt consumed for entity: {:?}", entity)
-                }
-                QuicActionError::ConnectionFailed(error) => {
-                    error!("Stream attempt failed: {:?}", error)
-                }
-                QuicActionError::Crashed(join_error) => {
-                    error!("Stream attempt crashed: {:?}", join_error)
-                }
-            }
+    fn insert_stream(
+        entity: &mut EntityCommands,
+        send: Self::Stream,
+        parent_id: QuicParentId,
+    ) -> StreamId {
+        info!("Spawning send stream with {parent_id}");
 
-            let mut error_entity = commands.entity(entity);
+        let stream_id = send.id();
+        entity.insert((send, QuicSession));
+        stream_id
+    }
+}
 
-            #[cfg(feature = "stream-errors")]
-            {
-                use {crate::common::attempt::QuicActionErrorComponent, std::time::SystemTime};
+impl StreamAttempt for QuicPeerStreamAttempt {
+    type Stream = QuicPeerStream;
 
-                let err_comp = QuicActionErrorComponent::new(e, SystemTime::now());
-                let err_bundle = (err_comp, *parent_id);
+    fn attempt(&mut self) -> &mut QuicActionAttempt<Option<Self::Stream>> {
+        self
+    }
 
-                error_entity.insert(err_bundle);
+    fn insert_stream(
+        entity: &mut EntityCommands,
+        peer_stream: Self::Stream,
+        parent_id: QuicParentId,
+    ) -> StreamId {
+        match peer_stream {
+            QuicPeerStream::Bidirectional(rec, send) => {
+                info!("Spawning peer (bidirectional) stream with {parent_id}");
+
+                let stream_id = rec.id();
+                entity.insert((rec, send, QuicSession));
+                stream_id
             }
+            QuicPeerStream::Receive(rec) => {
+                info!("Spawning peer (receive) stream with {parent_id}");
 
-            error_entity.remove::<QuicReceiveStreamAttempt>();
-
-            continue;
-        }
-
-        if let Some(rec) = res.unwrap() {
-            info!("Spawning receive stream with {parent_id}");
-
-            commands
-                .entity(entity)
-                .remove::<QuicReceiveStreamAttempt>()
-                .insert((rec, QuicSession));
-        }
-        // No new streams, delete attempt
-        else {
-            info!("No pending incoming streams, deleting attempt.");
-            commands.entity(entity).despawn();
+                let stream_id = rec.id();
+                entity.insert((rec, QuicSession));
+                stream_id
+            }
         }
     }
 }
 
 #[tracing::instrument(skip_all)]
-fn handle_peer_stream_attempt(
+fn handle_stream_attempts<A: StreamAttempt>(
     mut commands: Commands,
-    query: Query<(Entity, &mut QuicPeerStreamAttempt)>,
+    query: Query<(Entity, &mut A, Option<&ChildOf>)>,
 ) {
-    for entity_bundle in query {
-        let (entity, mut attempt) = entity_bundle;
+    for (entity, mut attempt, parent) in query {
+        let attempt = attempt.attempt();
         let parent_id = attempt.parent_id();
 
-        let res = attempt.attempt_result();
+        let stream = match attempt.attempt_result() {
+            Ok(stream) => stream,
+            Err(QuicActionError::Pending) => continue,
+            Err(e) => {
+                log_attempt_error(entity, &e);
 
-        if let Err(e) = res {
-            match &e {
-                QuicActionError::Pending => continue,
-                QuicActionError::Consumed => {
-                    error!("Stream attempt consumed for entity: {:?}", entity)
-                }
-                QuicActionError::ConnectionFailed(error) => {
-                    error!("Stream attempt failed: {:?}", error)
-                }
-                QuicActionError::Crashed(join_error) => {
-                    error!("Stream attempt crashed: {:?}", join_error)
-                }
-            }
+                let mut error_entity = commands.entity(entity);
 
-            let mut error_entity = commands.entity(entity);
+                #[cfg(feature = "stream-errors")]
+                {
+                    use {
+                        crate::common::attempt::QuicActionErrorComponent,
+                        std::time::SystemTime,
+                    };
 
-            #[cfg(feature = "stream-errors")]
-            {
-                use {crate::common::attempt::QuicActionErrorComponent, std::time::SystemTime};
+                    error_entity
+                        .insert(QuicActionErrorComponent::new(e, SystemTime::now()));
+                }
 
-                let err_comp = QuicActionErrorComponent::new(e, SystemTime::now());
-                let err_bundle = (err_comp, *parent_id);
+                error_entity.remove::<A>();
 
-                error_entity.insert(err_bundle);
+                continue;
             }
+        };
 
-            error_entity.remove::<QuicPeerStreamAttempt>();
-
+        // No new streams, delete attempt
+        let Some(stream) = stream else {
+            info!("No pending incoming streams, deleting attempt.");
+            commands.entity(entity).despawn();
             continue;
-        }
+        };
 
-        if let Some(peer_stream) = res.unwrap() {
-            let mut attempt_entity = commands.entity(entity);
-            attempt_entity.remove::<QuicPeerStreamAttempt>();
+        let mut stream_entity = commands.entity(entity);
+        stream_entity.remove::<A>();
+        let stream_id = A::insert_stream(&mut stream_entity, stream, parent_id);
 
-            match peer_stream {
-                QuicPeerStream::Bidirectional(quic_receive_stream, quic_send_stream) => {
-                    info!("Spawning peer (bidirectional) stream stream with {parent_id}");
-                    attempt_entity.insert((quic_receive_stream, quic_send_stream, QuicSession));
-                }
-                QuicPeerStream::Receive(quic_receive_stream) => {
-                    info!("Spawning peer (receive) stream stream with {parent_id}");
-                    attempt_entity.insert((quic_receive_stream, QuicSession));
-                }
-            }
+        trigger_stream_started(&mut commands, entity, stream_id, parent);
+    }
+}
+
+fn log_attempt_error(entity: Entity, error: &QuicActionError) {
+    match error {
+        QuicActionError::Pending => {}
+        QuicActionError::Consumed => {
+            error!("Stream attempt consumed for entity: {:?}", entity)
         }
-        // No new streams, delete attempt
-        else {
-            info!("No pending incoming streams, deleting attempt.");
-            commands.entity(entity).despawn();
+        QuicActionError::ConnectionFailed(error) => {
+            error!("Stream attempt failed: {:?}", error)
+        }
+        QuicActionError::Crashed(join_error) => {
+            error!("Stream attempt crashed: {:?}", join_error)
+        }
+        QuicActionError::StreamFailed(error) => {
+            error!("Stream attempt failed: {:?}", error)
+        }
+        QuicActionError::TimedOut(timeout) => {
+            error!("Stream attempt timed out after {:?}", timeout)
+        }
+        QuicActionError::Cancelled => {
+            info!("Stream attempt cancelled for entity: {:?}", entity)
         }
     }
 }
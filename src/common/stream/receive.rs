@@ -8,29 +8,37 @@ use bevy::{
     },
 };
 use bytes::Bytes;
-use s2n_quic::application::Error as ErrorCode;
 use s2n_quic::stream::ReceiveStream;
-use std::error::Error;
+use std::time::Instant;
 use tokio::{
-    runtime::Handle,
     select,
     sync::mpsc::{self, Receiver, Sender},
     time::Instant as TokioInstant,
 };
 
+#[cfg(feature = "serde")]
+use crate::common::stream::codec::{MessageCodec, QuicDecodeError};
 use crate::common::{
     HandleChannelError, QuicParentId,
+    error_code::QuicErrorCode,
+    orchestrator::AsyncOrchestrator,
     stream::{
-        disconnect::StreamDisconnectReason, id::StreamId, task_state::StreamTaskState,
+        CommandResponder, QuicStreamCommand,
+        control::{StreamControl, StreamControlReceiver, control_link},
+        disconnect::StreamDisconnectReason,
+        framing::FrameDecoder,
+        id::StreamId,
+        send_command,
+        task_state::StreamTaskState,
     },
 };
 
 type AddrResult = Result<std::net::SocketAddr, s2n_quic::connection::Error>;
 
-/// How many errors can be sent at a single time without being dropped
-const DEBUG_CHANNEL_SIZE: usize = 64;
-/// How many commands can be sent to the receive socket without being processed before being dropped
-const CONTROL_CHANNEL_SIZE: usize = 32;
+/// How many errors can be queued at a single time without being dropped
+const ERROR_QUEUE_SIZE: usize = 64;
+/// How many commands can be queued for the receive socket without being processed before being dropped
+const CONTROL_QUEUE_SIZE: usize = 32;
 /// How many messages can sit between async and bevy before being dropped
 const INBOUND_CHANNEL_SIZE: usize = 512;
 
@@ -41,40 +49,65 @@ const INBOUND_BUFF_SIZE: usize = 128;
 pub struct QuicReceiveStream {
     task_state: StreamTaskState,
     inbound_data: Receiver<RecvPacket>,
-    inbound_control: Sender<RecControlMessage>,
-    receive_errors: Receiver<Box<dyn Error + Send + Sync>>,
+    control: StreamControl<RecControlMessage>,
     stream_id: StreamId,
+    decoder: FrameDecoder,
+    /// Set once the peer violated the framing, no more messages will be decoded.
+    framing_violated: bool,
 }
 
 impl QuicReceiveStream {
-    pub fn new(runtime: Handle, rec: ReceiveStream, parent_id: QuicParentId) -> Self {
+    /// Runs the async side of the stream on the orchestrator, streams created by a
+    /// [QuicConnection][crate::common::connection::QuicConnection] share the orchestrator
+    /// of their connection.
+    pub fn new(
+        orchestrator: &AsyncOrchestrator,
+        rec: ReceiveStream,
+        parent_id: QuicParentId,
+    ) -> Self {
+        Self::with_received(orchestrator, rec, parent_id, None)
+    }
+
+    /// Like [new][Self::new()], `received` is data which was already read from
+    /// the stream and is handed out before anything else.
+    pub(crate) fn with_received(
+        orchestrator: &AsyncOrchestrator,
+        rec: ReceiveStream,
+        parent_id: QuicParentId,
+        received: Option<Bytes>,
+    ) -> Self {
         let stream_id = StreamId::new(parent_id, rec.id());
         let addr = rec.connection().remote_addr();
 
-        let (receive_error_sender, receive_errors) = mpsc::channel(DEBUG_CHANNEL_SIZE);
-        let (inbound_control, inbound_control_receiver) =
-            mpsc::channel(CONTROL_CHANNEL_SIZE);
+        let (control, control_receiver) =
+            control_link(CONTROL_QUEUE_SIZE, ERROR_QUEUE_SIZE);
         let (inbound_data_sender, inbound_data) = mpsc::channel(INBOUND_CHANNEL_SIZE);
 
+        if let Some(payload) = received {
+            let _ = inbound_data_sender.try_send(RecvPacket {
+                recv_at: Instant::now(),
+                payload,
+            });
+        }
+
         let task = RecTask {
             rec,
-            control: inbound_control_receiver,
+            control: control_receiver,
             inbound_sender: inbound_data_sender,
-            receive_errors: receive_error_sender,
             disconnect_flag: None,
             addr,
             stream_id,
         };
 
-        let rec_task = runtime.spawn(task.start());
-        let task_state = StreamTaskState::new(runtime, rec_task);
+        let task_state: StreamTaskState = orchestrator.spawn(task.start());
 
         Self {
             task_state,
             inbound_data,
-            inbound_control,
-            receive_errors,
+            control,
             stream_id,
+            decoder: FrameDecoder::default(),
+            framing_violated: false,
         }
     }
 
@@ -85,10 +118,128 @@ impl QuicReceiveStream {
 
     /// Receive multiple packets of data and push them to the given
     /// buffer for reading.
+    ///
+    /// This blocks the calling thread until at least one packet arrives or the stream
+    /// closes, use [try_recv_many][Self::try_recv_many()] from within systems.
     pub fn recv_many(&mut self, buffer: &mut Vec<RecvPacket>, limit: usize) -> usize {
         self.inbound_data.blocking_recv_many(buffer, limit)
     }
 
+    /// Receive up to `limit` packets which have already arrived and push them to the
+    /// given buffer for reading, returns the number of packets received.
+    ///
+    /// Never blocks, returns `0` if no packets are waiting.
+    pub fn try_recv_many(&mut self, buffer: &mut Vec<RecvPacket>, limit: usize) -> usize {
+        let mut count = 0;
+
+        while count < limit {
+            let Ok(packet) = self.inbound_data.try_recv() else {
+                break;
+            };
+
+            buffer.push(packet);
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Receives length prefixed messages sent with
+    /// [send_message][crate::common::stream::send::QuicSendStream::send_message()]
+    /// and pushes them to the given buffer, returns the number of messages received.
+    ///
+    /// Like [try_recv_many][Self::try_recv_many()] this never blocks.
+    ///
+    /// Each packet is exactly one message as it was sent. If the peer announces a message
+    /// larger than the max message size the peer is asked to reset the stream with
+    /// [QuicErrorCode::MESSAGE_TOO_LARGE] and no further messages are received.
+    ///
+    /// Framed messages should not be mixed with raw [recv][Self::recv()] calls on the same stream.
+    pub fn recv_messages(&mut self, buffer: &mut Vec<RecvPacket>, limit: usize) -> usize {
+        if self.framing_violated {
+            return 0;
+        }
+
+        // Messages left over from the last call were completed before anything new arrived.
+        let mut count = self.decode_messages(buffer, limit, Instant::now());
+
+        let mut chunks = Vec::new();
+        self.try_recv_many(&mut chunks, INBOUND_BUFF_SIZE);
+
+        for chunk in chunks {
+            if self.framing_violated {
+                break;
+            }
+
+            self.decoder.push(&chunk.payload);
+            count += self.decode_messages(buffer, limit - count, chunk.recv_at);
+        }
+
+        count
+    }
+
+    fn decode_messages(
+        &mut self,
+        buffer: &mut Vec<RecvPacket>,
+        limit: usize,
+        recv_at: Instant,
+    ) -> usize {
+        let mut count = 0;
+
+        while count < limit && !self.framing_violated {
+            match self.decoder.next_message() {
+                Ok(Some(payload)) => {
+                    buffer.push(RecvPacket { recv_at, payload });
+                    count += 1;
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    error!("Receiver ID: {}, stopping stream: {err}", self.stream_id);
+                    self.framing_violated = true;
+                    let _ = self.stop_send(QuicErrorCode::MESSAGE_TOO_LARGE);
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Receives messages with [recv_messages][Self::recv_messages()] and decodes them
+    /// with the codec, returns the number of messages received.
+    ///
+    /// Messages which fail to decode are pushed as a [QuicDecodeError] and don't affect
+    /// the rest of the stream.
+    #[cfg(feature = "serde")]
+    pub fn recv_typed<C: MessageCodec, T: serde::de::DeserializeOwned>(
+        &mut self,
+        buffer: &mut Vec<Result<T, QuicDecodeError>>,
+        limit: usize,
+    ) -> usize {
+        let mut messages = Vec::new();
+        let count = self.recv_messages(&mut messages, limit);
+        let stream_id = self.stream_id;
+
+        buffer.extend(messages.into_iter().map(|message| {
+            C::decode(&message.payload).map_err(|source| QuicDecodeError {
+                stream_id,
+                payload: message.payload,
+                source,
+            })
+        }));
+
+        count
+    }
+
+    /// The largest message [recv_messages][Self::recv_messages()] will accept,
+    /// defaults to [DEFAULT_MAX_MESSAGE_SIZE][crate::common::stream::framing::DEFAULT_MAX_MESSAGE_SIZE].
+    pub fn max_message_size(&self) -> usize {
+        self.decoder.max_message_size()
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.decoder.set_max_message_size(max_message_size);
+    }
+
     /// Returns `true` if this stream is still open
     pub fn is_open(&self) -> bool {
         !self.task_state.is_finished()
@@ -97,28 +248,30 @@ impl QuicReceiveStream {
     /// Notifies the peer to stop sending data on the stream.
     ///
     /// This requests the peer to finish the stream as soon as possible by issuing a reset with the provided error_code.
-    pub fn stop_send(&mut self, err_code: ErrorCode) {
-        let Err(_e) = self
-            .inbound_control
-            .blocking_send(RecControlMessage::StopSend(err_code))
-        else {
-            return;
-        };
+    ///
+    /// The returned command completes once the request has been queued for the peer.
+    /// It fails straight away if the control channel is full, or if the async task
+    /// has been shut down, already quit, or crashed.
+    pub fn stop_send(&mut self, err_code: QuicErrorCode) -> QuicStreamCommand {
+        if self.control.is_closed() {
+            warn!(
+                "Stop_send() called on stopped connection with ID: {}.",
+                self.stream_id
+            );
+        }
 
-        warn!(
-            "Stop_send() called on stopped connection with ID: {}.",
-            self.stream_id
-        );
+        send_command(
+            self.task_state.runtime(),
+            &self.control,
+            self.parent_id(),
+            |responder| RecControlMessage::StopSend(err_code, responder),
+        )
     }
 
     /// Outputs any outstanding errors that have happened on the
     /// async side of this stream.
     pub fn log_outstanding_errors(&mut self) {
-        while !self.receive_errors.is_empty() {
-            let Some(err) = self.receive_errors.blocking_recv() else {
-                continue;
-            };
-
+        while let Some(err) = self.control.pop_error() {
             error!(
                 "Receiver ID: {}, encountered error:\n{}",
                 self.stream_id, err
@@ -144,14 +297,13 @@ impl QuicReceiveStream {
 }
 
 enum RecControlMessage {
-    StopSend(ErrorCode),
+    StopSend(QuicErrorCode, CommandResponder),
 }
 
 struct RecTask {
     rec: ReceiveStream,
-    control: Receiver<RecControlMessage>,
+    control: StreamControlReceiver<RecControlMessage>,
     inbound_sender: Sender<RecvPacket>,
-    receive_errors: Sender<Box<dyn Error + Send + Sync>>,
     disconnect_flag: Option<StreamDisconnectReason>,
     addr: AddrResult,
     stream_id: StreamId,
@@ -180,12 +332,16 @@ impl RecTask {
                 cmd_opt = self.control.recv() => {
                     if let Some(cmd) = cmd_opt {
                         match cmd {
-                            RecControlMessage::StopSend(error_code) => {
+                            RecControlMessage::StopSend(error_code, responder) => {
                                 self.disconnect_flag = Some(StreamDisconnectReason::UserClosed);
 
-                                if let Err(stream_err) = self.rec.stop_sending(error_code) {
+                                let res = self.rec.stop_sending(error_code.into());
+
+                                if let Err(stream_err) = res {
                                     warn!("Stream error on receive stop_send():\n{stream_err}");
                                 }
+
+                                let _ = responder.send(res.map_err(Into::into));
                             }
                         }
                     }
@@ -203,7 +359,7 @@ impl RecTask {
             }
         }
 
-        let _send_res = self.rec.stop_sending(ErrorCode::UNKNOWN);
+        let _send_res = self.rec.stop_sending(QuicErrorCode::NO_ERROR.into());
         let instant = TokioInstant::now();
 
         // Empty out receiver
@@ -253,19 +409,14 @@ impl RecTask {
                 match e {
                     s2n_quic::stream::Error::ConnectionError { error, .. } => {
                         error!("Receive stream connection error: {error}");
-                        self.disconnect_flag =
-                            Some(StreamDisconnectReason::ConnectionError(error));
                     }
 
                     s2n_quic::stream::Error::InvalidStream { source, .. } => {
                         error!("Invalid receive stream: {source}");
-                        self.disconnect_flag =
-                            Some(StreamDisconnectReason::InvalidStream);
                     }
 
                     s2n_quic::stream::Error::StreamReset { error, source, .. } => {
                         error!("Receive stream reset: {error}, Source: {source}");
-                        self.disconnect_flag = Some(StreamDisconnectReason::Reset(error));
                     }
 
                     _ => {
@@ -273,7 +424,11 @@ impl RecTask {
                     }
                 }
 
-                self.receive_errors.try_send(Box::new(e)).handle_err();
+                if let Some(reason) = StreamDisconnectReason::from_stream_error(&e) {
+                    self.disconnect_flag = Some(reason);
+                }
+
+                self.control.try_send_error(Box::new(e)).handle_err();
             }
         }
     }
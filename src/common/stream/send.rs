@@ -3,23 +3,32 @@ use bevy::log::tracing::{self};
 use bevy::log::{error, info, warn};
 use bytes::Bytes;
 use s2n_quic::stream::SendStream;
-use std::error::Error;
-use tokio::runtime::Handle;
 use tokio::select;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 
+use crate::common::error_code::QuicErrorCode;
+use crate::common::orchestrator::AsyncOrchestrator;
+#[cfg(feature = "serde")]
+use crate::common::stream::codec::{MessageCodec, TypedSendError};
+use crate::common::stream::control::{
+    StreamControl, StreamControlReceiver, control_link,
+};
 use crate::common::stream::disconnect::StreamDisconnectReason;
+use crate::common::stream::framing::{
+    DEFAULT_MAX_MESSAGE_SIZE, FramingError, SendMessageError, encode_header,
+};
 use crate::common::stream::id::StreamId;
 use crate::common::stream::task_state::StreamTaskState;
+use crate::common::stream::{CommandResponder, QuicStreamCommand, send_command};
 use crate::common::{HandleChannelError, QuicParentId};
 
 type AddrResult = Result<std::net::SocketAddr, s2n_quic::connection::Error>;
 
-/// How many errors can be sent at a single time without being dropped
-const DEBUG_CHANNEL_SIZE: usize = 32;
-/// How many commands can be sent to the send socket without being processed before being dropped
-const CONTROL_CHANNEL_SIZE: usize = 32;
+/// How many errors can be queued at a single time without being dropped
+const ERROR_QUEUE_SIZE: usize = 32;
+/// How many commands can be queued for the send socket without being processed before being dropped
+const CONTROL_QUEUE_SIZE: usize = 32;
 /// How many messages can sit between async and bevy before being dropped
 const OUTBOUND_CHANNEL_SIZE: usize = 512;
 
@@ -32,60 +41,89 @@ const MAX_OUTBOUND_BUF_SIZE: usize = 128;
 pub struct QuicSendStream {
     task_state: StreamTaskState,
     outbound_data: Sender<Bytes>,
-    outbound_control: Sender<SendControlMessage>,
-    send_errors: Receiver<Box<dyn Error + Send + Sync>>,
+    control: StreamControl<SendControlMessage>,
     stream_id: StreamId,
+    max_message_size: usize,
 }
 
 impl QuicSendStream {
-    pub fn new(runtime: Handle, send: SendStream, parent_id: QuicParentId) -> Self {
+    /// Runs the async side of the stream on the orchestrator, streams created by a
+    /// [QuicConnection][crate::common::connection::QuicConnection] share the orchestrator
+    /// of their connection.
+    pub fn new(
+        orchestrator: &AsyncOrchestrator,
+        send: SendStream,
+        parent_id: QuicParentId,
+    ) -> Self {
         let stream_id = StreamId::new(parent_id, send.id());
         let addr = send.connection().local_addr();
 
-        let (send_error_sender, send_errors) = mpsc::channel(DEBUG_CHANNEL_SIZE);
-        let (outbound_control, outbound_control_receiver) =
-            mpsc::channel(CONTROL_CHANNEL_SIZE);
+        let (control, control_receiver) =
+            control_link(CONTROL_QUEUE_SIZE, ERROR_QUEUE_SIZE);
         let (outbound_data, outbound_data_receiver) =
             mpsc::channel(OUTBOUND_CHANNEL_SIZE);
 
         let task = SendTask {
             send,
-            control: outbound_control_receiver,
+            control: control_receiver,
             outbound_receiver: outbound_data_receiver,
-            send_errors: send_error_sender,
             disconnect_flag: None,
             addr,
             stream_id,
         };
 
-        let send_task = runtime.spawn(task.start());
-        let task_state = StreamTaskState::new(runtime, send_task);
+        let task_state: StreamTaskState = orchestrator.spawn(task.start());
 
         Self {
             task_state,
             outbound_data,
-            outbound_control,
-            send_errors,
+            control,
             stream_id,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
         }
     }
 
-    /// Returns `Some(())` in the event the close event was successful, if it wasn't
-    /// it's due to the Receiver of the message being dropped. In which case
-    /// it's likely the async task has been shut down, already quit, or crashed.
-    pub fn close(&mut self) -> Option<()> {
-        self.outbound_control
-            .blocking_send(SendControlMessage::CloseAndQuit)
-            .ok()
+    /// Finishes the stream once all queued data has been sent, then quits the async task.
+    ///
+    /// The returned command completes once the peer has acknowledged all the data on
+    /// the stream. It fails straight away if the control channel is full, or if the
+    /// async task has been shut down, already quit, or crashed.
+    pub fn close(&mut self) -> QuicStreamCommand {
+        send_command(
+            self.task_state.runtime(),
+            &self.control,
+            self.parent_id(),
+            SendControlMessage::CloseAndQuit,
+        )
     }
 
-    /// Returns `Some(())` in the event the flush event was successful, if it wasn't
-    /// it's due to the Receiver of the message being dropped. In which case
-    /// it's likely the async task has been shut down, already quit, or crashed.
-    pub fn flush(&mut self) -> Option<()> {
-        self.outbound_control
-            .blocking_send(SendControlMessage::Flush)
-            .ok()
+    /// Aborts the stream with an application error code, the peer receives the code
+    /// as a reset. Any data still queued on this side is discarded.
+    ///
+    /// The returned command completes once the reset has been queued for the peer.
+    /// It fails straight away if the control channel is full, or if the async task
+    /// has been shut down, already quit, or crashed.
+    pub fn reset(&mut self, code: QuicErrorCode) -> QuicStreamCommand {
+        send_command(
+            self.task_state.runtime(),
+            &self.control,
+            self.parent_id(),
+            |responder| SendControlMessage::Reset(code, responder),
+        )
+    }
+
+    /// Flushes the data sent so far.
+    ///
+    /// The returned command completes once the peer has acknowledged all the data
+    /// sent before the flush. It fails straight away if the control channel is full,
+    /// or if the async task has been shut down, already quit, or crashed.
+    pub fn flush(&mut self) -> QuicStreamCommand {
+        send_command(
+            self.task_state.runtime(),
+            &self.control,
+            self.parent_id(),
+            SendControlMessage::Flush,
+        )
     }
 
     /// Checks if the async task for the stream is still running, in which case
@@ -126,14 +164,97 @@ impl QuicSendStream {
         res
     }
 
+    /// Sends the data as a single length prefixed message, the peer will receive
+    /// exactly this message from [recv_messages][crate::common::stream::receive::QuicReceiveStream::recv_messages()].
+    ///
+    /// Framed messages should not be mixed with raw [send][Self::send()] calls on the same stream.
+    pub fn send_message(&mut self, data: Bytes) -> Result<(), SendMessageError> {
+        if data.len() > self.max_message_size {
+            return Err(FramingError::MessageTooLarge {
+                size: data.len() as u64,
+                max: self.max_message_size,
+            }
+            .into());
+        }
+
+        // The header and message have to go through the channel back to back,
+        // so reserve both slots up front.
+        let mut permits = match self.outbound_data.try_reserve_many(2) {
+            Ok(permits) => permits,
+            Err(TrySendError::Full(_)) => return Err(SendMessageError::Full(data)),
+            Err(TrySendError::Closed(_)) => return Err(SendMessageError::Closed(data)),
+        };
+
+        let header = encode_header(data.len());
+
+        if let (Some(header_permit), Some(data_permit)) = (permits.next(), permits.next())
+        {
+            header_permit.send(header);
+            data_permit.send(data);
+        }
+
+        Ok(())
+    }
+
+    /// Takes a vector of messages and sends them with [send_message][Self::send_message()]
+    /// until an error is hit or until the vector is emptied.
+    ///
+    /// Messages larger than the max message size are dropped.
+    pub fn send_messages_drain(
+        &mut self,
+        data: &mut Vec<Bytes>,
+    ) -> Result<(), SendMessageError> {
+        let mut sent_count = 0;
+        let mut res = Ok(());
+
+        for item in data.iter() {
+            res = self.send_message(item.clone());
+
+            match res {
+                Ok(()) => sent_count += 1,
+                Err(SendMessageError::Framing(_)) => {
+                    sent_count += 1;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+
+        data.drain(..sent_count);
+
+        res
+    }
+
+    /// Encodes the value with the codec and sends it as a single message,
+    /// see [send_message][Self::send_message()].
+    ///
+    /// ```ignore
+    /// stream.send_typed::<BincodeCodec, _>(&PlayerInput { jump: true })?;
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn send_typed<C: MessageCodec, T: serde::Serialize>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), TypedSendError> {
+        let data = C::encode(value).map_err(TypedSendError::Encode)?;
+        self.send_message(data)?;
+        Ok(())
+    }
+
+    /// The largest message [send_message][Self::send_message()] will accept,
+    /// defaults to [DEFAULT_MAX_MESSAGE_SIZE].
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
     /// Outputs any outstanding errors that have happened on the
     /// async side of this stream.
     pub fn log_outstanding_errors(&mut self) {
-        while !self.send_errors.is_empty() {
-            let Some(err) = self.send_errors.blocking_recv() else {
-                continue;
-            };
-
+        while let Some(err) = self.control.pop_error() {
             error!("Sender ID: {}, encountered error:\n{}", self.stream_id, err);
         }
     }
@@ -157,9 +278,8 @@ impl QuicSendStream {
 
 struct SendTask {
     send: SendStream,
-    control: Receiver<SendControlMessage>,
+    control: StreamControlReceiver<SendControlMessage>,
     outbound_receiver: Receiver<Bytes>,
-    send_errors: Sender<Box<dyn Error + Send + Sync>>,
     disconnect_flag: Option<StreamDisconnectReason>,
     addr: AddrResult,
     stream_id: StreamId,
@@ -199,7 +319,6 @@ impl SendTask {
                                     "Send stream is in an invalid state, quitting:\n{}",
                                     source
                                 );
-                                self.disconnect_flag = Some(StreamDisconnectReason::InvalidStream)
                             }
 
                             s2n_quic::stream::Error::StreamReset {
@@ -209,7 +328,6 @@ impl SendTask {
                                     "Send stream has encountered a stream reset:\n{}",
                                     error
                                 );
-                                self.disconnect_flag = Some(StreamDisconnectReason::Reset(error));
                             }
 
                             _ => {
@@ -220,15 +338,19 @@ impl SendTask {
                             }
                         }
 
-                        self.send_errors.try_send(Box::new(err)).handle_err();
+                        self.handle_stream_error(err);
                     }
                 }
 
                 cmd_opt = self.control.recv() => {
                     if let Some(cmd) = cmd_opt {
                         match cmd {
-                            SendControlMessage::CloseAndQuit => {
-                                let res = self.send.close().await;
+                            SendControlMessage::CloseAndQuit(responder) => {
+                                let res = match self.send_queued(&mut send_buf).await {
+                                    Ok(()) => self.send.close().await,
+                                    Err(e) => Err(e),
+                                };
+                                self.disconnect_flag = Some(StreamDisconnectReason::UserClosed);
 
                                 if let Err(e) = res {
                                     error!(
@@ -236,14 +358,34 @@ impl SendTask {
                                         e
                                     );
 
-                                    self.send_errors.try_send(Box::new(e)).handle_err();
+                                    self.handle_stream_error(e);
                                 }
 
-                                self.disconnect_flag = Some(StreamDisconnectReason::UserClosed);
+                                let _ = responder.send(res.map_err(Into::into));
+                            }
+
+                            SendControlMessage::Reset(code, responder) => {
+                                let discarded = self.discard_queued();
+                                let res = self.send.reset(code.into());
+
+                                info!(
+                                    "Send stream reset with code {}, discarded {} queued messages",
+                                    code, discarded
+                                );
+
+                                if let Err(e) = res {
+                                    warn!("Send stream errored when resetting stream:\n{}", e);
+                                }
+
+                                let _ = responder.send(res.map_err(Into::into));
+                                self.disconnect_flag = Some(StreamDisconnectReason::UserReset(code));
                             }
 
-                            SendControlMessage::Flush => {
-                                let res = self.send.flush().await;
+                            SendControlMessage::Flush(responder) => {
+                                let res = match self.send_queued(&mut send_buf).await {
+                                    Ok(()) => self.send.flush().await,
+                                    Err(e) => Err(e),
+                                };
 
                                 if let Err(e) = res {
                                     error!(
@@ -251,8 +393,10 @@ impl SendTask {
                                         e
                                     );
 
-                                    self.send_errors.try_send(Box::new(e)).handle_err();
+                                    self.handle_stream_error(e);
                                 }
+
+                                let _ = responder.send(res.map_err(Into::into));
                             }
                         }
                     }
@@ -290,9 +434,54 @@ impl SendTask {
             StreamDisconnectReason::NoReason
         }
     }
+    /// Ends the stream if the error closed it, a reset by the peer keeps its error code.
+    fn handle_stream_error(&mut self, err: s2n_quic::stream::Error) {
+        if let Some(reason) = StreamDisconnectReason::from_stream_error(&err) {
+            self.disconnect_flag = Some(reason);
+        }
+
+        self.control.try_send_error(Box::new(err)).handle_err();
+    }
+
+    /// Sends everything already queued, so a close or flush never overtakes data
+    /// which was sent before it.
+    async fn send_queued(
+        &mut self,
+        send_buf: &mut Vec<Bytes>,
+    ) -> Result<(), s2n_quic::stream::Error> {
+        loop {
+            while send_buf.len() < MAX_OUTBOUND_BUF_SIZE
+                && let Ok(data) = self.outbound_receiver.try_recv()
+            {
+                send_buf.push(data);
+            }
+
+            if send_buf.is_empty() {
+                return Ok(());
+            }
+
+            let res = self.send.send_vectored(send_buf).await;
+            send_buf.clear();
+            res?;
+        }
+    }
+
+    /// Drops everything queued to be sent, returning how many messages were dropped.
+    fn discard_queued(&mut self) -> usize {
+        self.outbound_receiver.close();
+
+        let mut discarded = 0;
+
+        while self.outbound_receiver.try_recv().is_ok() {
+            discarded += 1;
+        }
+
+        discarded
+    }
 }
 
 enum SendControlMessage {
-    CloseAndQuit,
-    Flush,
+    CloseAndQuit(CommandResponder),
+    Reset(QuicErrorCode, CommandResponder),
+    Flush(CommandResponder),
 }
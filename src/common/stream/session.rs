@@ -8,7 +8,7 @@ use bevy::{
         component::Component,
         entity::Entity,
         observer::On,
-        query::With,
+        query::{With, Without},
         system::{Commands, Query},
         world::World,
     },
@@ -16,25 +16,48 @@ use bevy::{
 };
 use std::time::Instant;
 
-use crate::common::stream::{
-    disconnect::StreamDisconnectReason, receive::QuicReceiveStream, send::QuicSendStream,
+use crate::common::{
+    connection::{
+        QuicConnection,
+        datagram::MIN_DATAGRAM_MTU,
+        session::{aeronet_connection_recv, aeronet_connection_send},
+    },
+    error_code::QuicErrorCode,
+    stream::{
+        channel::{QuicAwaitingChannelHeader, QuicChannelHeader},
+        disconnect::StreamDisconnectReason,
+        receive::QuicReceiveStream,
+        send::QuicSendStream,
+    },
 };
 
 const MIN_MTU: usize = 1200;
 const MAX_PACKET_TRANSFER: usize = 512;
 const PACKET_WARN_THRESH: usize = 400;
 
-/// The disconnect code which will be sent by QUIC when a disconnect is called
-/// via the [Disconnect](https://docs.rs/aeronet_io/latest/aeronet_io/connection/struct.Disconnect.html)
-/// event on any ReceiveStream.
-pub const AERONET_DISCONNECT_CODE: u32 = 12345;
+/// Sessions hold off on their streams until the channel header has been read or sent
+type SessionRecvFilter = (With<QuicSession>, Without<QuicAwaitingChannelHeader>);
+type SessionSendFilter = (With<QuicSession>, Without<QuicChannelHeader>);
 
 /// The component which is added once a stream of any kind has been
 /// successfully made.
+///
+/// Each packet of the session is sent as one length prefixed message, so packet
+/// boundaries are kept intact across the stream.
 #[derive(Component, Default)]
 #[require(Session::new(Instant::now(), MIN_MTU))]
 pub struct QuicSession;
 
+/// Add this component to a [QuicConnection] entity to use it as an unreliable
+/// aeronet [Session](https://docs.rs/aeronet_io/latest/aeronet_io/struct.Session.html).
+///
+/// Packets in the session are sent and received as QUIC datagrams, so they may
+/// arrive out of order or not at all, but will never be held up by a lost packet.
+/// The connection's endpoint must have datagrams enabled.
+#[derive(Component, Default)]
+#[require(Session::new(Instant::now(), MIN_DATAGRAM_MTU))]
+pub struct QuicDatagramSession;
+
 // NOTE:
 // Aeronet_IO is being left as a hard dependency as of now,
 // the cost of having to convert to Aeronet structures is
@@ -54,14 +77,28 @@ pub struct QuicAeronetPacketPlugin;
 
 impl Plugin for QuicAeronetPacketPlugin {
     fn build(&self, app: &mut bevy::app::App) {
-        app.add_systems(PreUpdate, (aeronet_session_recv, aeronet_session_send));
+        app.add_systems(
+            PreUpdate,
+            (
+                aeronet_session_recv,
+                aeronet_session_send,
+                aeronet_datagram_recv,
+                aeronet_datagram_send,
+                aeronet_connection_recv,
+                aeronet_connection_send,
+            ),
+        );
     }
 }
 
 /// The plugin which handles sending and receiving the [Disconnect](https://docs.rs/aeronet_io/latest/aeronet_io/connection/struct.Disconnect.html)
 /// and [Disconnected](https://docs.rs/aeronet_io/latest/aeronet_io/connection/struct.Disconnected.html) events.
-/// Disconnects triggered via the [Disconnect](https://docs.rs/aeronet_io/latest/aeronet_io/connection/struct.Disconnect.html)
-/// event will use the default
+///
+/// A [Disconnect](https://docs.rs/aeronet_io/latest/aeronet_io/connection/struct.Disconnect.html)
+/// on a [QuicConnection] closes it with [QuicConnection::close_with_reason()], the peer sees
+/// the same reason in `DisconnectReason::ByPeer`. The connection is closed with the code
+/// registered under the reason's name if there is one, [QuicErrorCode::AERONET_DISCONNECT]
+/// otherwise. Streams are stopped with [QuicErrorCode::AERONET_DISCONNECT].
 pub struct QuicAeronetEventPlugin;
 
 impl Plugin for QuicAeronetEventPlugin {
@@ -76,14 +113,14 @@ impl Plugin for QuicAeronetEventPlugin {
 
 #[tracing::instrument(skip_all)]
 fn aeronet_session_recv(
-    query: Query<(&mut Session, &mut QuicReceiveStream), With<QuicSession>>,
+    query: Query<(&mut Session, &mut QuicReceiveStream), SessionRecvFilter>,
 ) {
     let mut buffer = Vec::new();
 
     for entity in query {
         let (mut session, mut rec) = entity;
 
-        let size = rec.recv_many(&mut buffer, MAX_PACKET_TRANSFER);
+        let size = rec.recv_messages(&mut buffer, MAX_PACKET_TRANSFER);
 
         if size >= PACKET_WARN_THRESH {
             #[cfg(feature = "performance-warns")]
@@ -110,13 +147,13 @@ fn aeronet_session_recv(
 
 #[tracing::instrument(skip_all)]
 fn aeronet_session_send(
-    query: Query<(&mut Session, &mut QuicSendStream), With<QuicSession>>,
+    query: Query<(&mut Session, &mut QuicSendStream), SessionSendFilter>,
 ) {
     for entity in query {
         let (mut session, mut send) = entity;
         let parent_id = send.parent_id();
 
-        let res = send.send_many_drain(&mut session.send);
+        let res = send.send_messages_drain(&mut session.send);
 
         #[cfg(feature = "performance-warns")]
         if res.is_err() {
@@ -129,6 +166,50 @@ fn aeronet_session_send(
     }
 }
 
+#[tracing::instrument(skip_all)]
+fn aeronet_datagram_recv(
+    query: Query<(&mut Session, &mut QuicConnection), With<QuicDatagramSession>>,
+) {
+    for (mut session, mut connection) in query {
+        let size = connection.recv_datagrams(&mut session.recv, MAX_PACKET_TRANSFER);
+
+        #[cfg(feature = "performance-warns")]
+        if size >= PACKET_WARN_THRESH {
+            warn!(
+                "Datagram input is unexpectedly high. If the max transfer rate is exceeded this could cause delays in packet delivery. \nMax: {}\nCurrent: {}",
+                MAX_PACKET_TRANSFER, size
+            );
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn aeronet_datagram_send(
+    query: Query<(&mut Session, &QuicConnection), With<QuicDatagramSession>>,
+) {
+    for (mut session, connection) in query {
+        if let Some(mtu) = connection.max_datagram_size()
+            && mtu != session.mtu()
+        {
+            let _ = session.set_mtu(mtu);
+        }
+
+        let mut packets = session.send.drain(..);
+
+        for packet in packets.by_ref() {
+            if let Err(e) = connection.send_datagram(packet) {
+                warn!(
+                    "Unable to send datagram for '{}', {} datagrams will be dropped: {}",
+                    connection.id(),
+                    packets.len() + 1,
+                    e
+                );
+                break;
+            }
+        }
+    }
+}
+
 fn fire_send_disconnect_events(
     mut cmd: Commands,
     query: Query<(&mut QuicReceiveStream, Entity), With<Session>>,
@@ -161,18 +242,23 @@ fn fire_disconnect(cmd: &mut Commands, entity: Entity, reason: StreamDisconnectR
 fn add_disconnect_handler(world: &mut World) {
     world.add_observer(
         |event: On<Disconnect>,
-         rec_query: Query<(&mut QuicReceiveStream, Entity)>,
-         send_query: Query<(&mut QuicSendStream, Entity)>| {
-            for (mut stream, entity) in rec_query {
-                if entity == event.entity {
-                    stream.stop_send(AERONET_DISCONNECT_CODE.into());
-                }
+         connection_query: Query<&QuicConnection>,
+         mut rec_query: Query<&mut QuicReceiveStream>,
+         mut send_query: Query<&mut QuicSendStream>| {
+            // Reasons named after a registered code are closed with that code
+            if let Ok(connection) = connection_query.get(event.entity) {
+                let code = QuicErrorCode::from_name(&event.reason)
+                    .unwrap_or(QuicErrorCode::AERONET_DISCONNECT);
+
+                connection.close_with_reason(code, event.reason.clone());
+            }
+
+            if let Ok(mut stream) = rec_query.get_mut(event.entity) {
+                let _ = stream.stop_send(QuicErrorCode::AERONET_DISCONNECT);
             }
 
-            for (mut stream, entity) in send_query {
-                if entity == event.entity {
-                    stream.close();
-                }
+            if let Ok(mut stream) = send_query.get_mut(event.entity) {
+                let _ = stream.close();
             }
         },
     );
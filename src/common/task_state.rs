@@ -1,16 +1,18 @@
-use std::{error::Error, sync::Arc};
-use tokio::{runtime::Handle, task::JoinHandle};
+use std::{
+    error::Error,
+    sync::{Arc, OnceLock},
+};
+use tokio::runtime::Handle;
 
-// TODO: Change this to use Oncelock<T>
-// we're moving to a central orchestrator architecture for
-// async tasks.
+/// The sync side view of a task spawned on an
+/// [AsyncOrchestrator][crate::common::orchestrator::AsyncOrchestrator],
+/// holds the output of the task once it has finished.
 #[derive(Debug)]
 pub(crate) struct QuicTaskState<T>
 where
     T: Clone + From<Arc<dyn Error + Send + Sync>>,
 {
-    task: Option<JoinHandle<T>>,
-    disconnect_reason: Option<T>,
+    disconnect_reason: Arc<OnceLock<T>>,
     runtime: Handle,
 }
 
@@ -18,53 +20,22 @@ impl<T> QuicTaskState<T>
 where
     T: Clone + From<Arc<dyn Error + Send + Sync>>,
 {
-    pub fn new(runtime: Handle, task: JoinHandle<T>) -> Self {
+    pub fn new(runtime: Handle, disconnect_reason: Arc<OnceLock<T>>) -> Self {
         Self {
-            task: Some(task),
-            disconnect_reason: None,
+            disconnect_reason,
             runtime,
         }
     }
 
+    pub fn runtime(&self) -> &Handle {
+        &self.runtime
+    }
+
     pub fn is_finished(&self) -> bool {
-        if let Some(join) = &self.task {
-            return join.is_finished();
-        }
-        true
+        self.disconnect_reason.get().is_some()
     }
-}
 
-impl<T: Clone> QuicTaskState<T>
-where
-    T: From<Arc<dyn Error + Send + Sync>>,
-{
     pub fn get_disconnect_reason(&mut self) -> Option<T> {
-        if let Some(join_ref) = self.task.as_ref() {
-            if !join_ref.is_finished() {
-                return None;
-            }
-            let join = self.task.take().unwrap();
-            let join_res = self.runtime.block_on(join);
-            if let Err(reason) = join_res {
-                self.disconnect_reason = Some(T::from(Arc::new(reason)));
-                return self.disconnect_reason.clone();
-            }
-            self.disconnect_reason = Some(join_res.unwrap());
-            return self.disconnect_reason.clone();
-        }
-        if self.disconnect_reason.is_none() {
-            #[cfg(debug_assertions)]
-            panic!(
-                "{} is in invalid state, neither a join handle nor a disconnect reason was found.",
-                std::any::type_name::<T>()
-            );
-
-            #[cfg(not(debug_assertions))]
-            bevy::log::error!(
-                "{} task is in invalid state, neither a join handle nor a disconnect reason was found. Returning none, this may result in weird behaviour.",
-                std::any::type_name::<T>()
-            );
-        }
-        self.disconnect_reason.clone()
+        self.disconnect_reason.get().cloned()
     }
 }
@@ -34,16 +34,21 @@ use bevy::app::{PluginGroup, PluginGroupBuilder};
 
 use crate::{
     async_plugin::QuicAsyncPlugin,
-    client::acceptor::SimpleClientAcceptorPlugin,
+    client::reconnect::QuicReconnectPlugin,
     common::{
-        connection::plugin::ConnectionAttemptPlugin,
+        connection::{
+            event::ConnectionEventPlugin, plugin::ConnectionAttemptPlugin,
+            stats::ConnectionStatsPlugin,
+        },
         plugin::DisconnectHandlerPlugin,
         stream::{
+            acceptor::StreamAcceptorPlugin,
+            channel::StreamChannelPlugin,
             plugin::StreamAttemptPlugin,
             session::{QuicAeronetEventPlugin, QuicAeronetPacketPlugin},
         },
     },
-    server::acceptor::SimpleServerAcceptorPlugin,
+    server::{acceptor::SimpleServerAcceptorPlugin, shutdown::ServerShutdownPlugin},
 };
 
 /// The default set of plugins needed to make the Bevy Quic components
@@ -57,8 +62,13 @@ impl PluginGroup for QuicDefaultPlugins {
             .add(ConnectionAttemptPlugin)
             .add(StreamAttemptPlugin)
             .add(SimpleServerAcceptorPlugin)
-            .add(SimpleClientAcceptorPlugin)
+            .add(ServerShutdownPlugin)
+            .add(StreamAcceptorPlugin)
+            .add(StreamChannelPlugin)
             .add(DisconnectHandlerPlugin)
+            .add(QuicReconnectPlugin)
+            .add(ConnectionStatsPlugin)
+            .add(ConnectionEventPlugin)
     }
 }
 
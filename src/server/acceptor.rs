@@ -3,27 +3,29 @@ use bevy::{
     ecs::{
         entity::Entity,
         hierarchy::ChildOf,
-        query::With,
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query},
     },
-    log::{error, tracing},
+    log::{error, info},
 };
 
 use crate::{
-    common::connection::QuicConnection,
+    async_plugin::quic_tick_ready,
     server::{QuicServer, marker::QuicServerMarker},
 };
 
-/// This plugin makes servers automatically accept all incoming
-/// connections and streams and spawns them as components parented
-/// to their [QuicServer]s and [QuicConnection]s in the ECS world.
+/// This plugin makes servers automatically accept all incoming connections
+/// admitted by their [QuicAdmissionPolicy][crate::server::admission::QuicAdmissionPolicy]
+/// and spawns them as components parented to their [QuicServer]s in the ECS world.
+///
+/// Streams on those connections are accepted by the
+/// [StreamAcceptorPlugin][crate::common::stream::acceptor::StreamAcceptorPlugin].
 #[derive(Debug)]
 pub struct SimpleServerAcceptorPlugin;
 
 impl Plugin for SimpleServerAcceptorPlugin {
     fn build(&self, app: &mut bevy::app::App) {
-        app.add_systems(Update, accept_connections)
-            .add_systems(Update, accept_streams);
+        app.add_systems(Update, accept_connections.run_if(quic_tick_ready));
     }
 }
 
@@ -41,39 +43,18 @@ fn accept_connections(mut commands: Commands, servers: Query<(&mut QuicServer, E
         match conn {
             super::ConnectionPoll::None => continue,
             super::ConnectionPoll::ServerClosed => continue,
+            super::ConnectionPoll::Rejected { remote_addr, code } => {
+                info!("Refused connection from {} with code {}", remote_addr, code);
+            }
             super::ConnectionPoll::NewConnection(quic_connection) => {
+                let peer_identity = quic_connection.peer_identity().cloned();
                 let bundle = (quic_connection, QuicServerMarker, ChildOf(entity));
-                commands.spawn(bundle);
-            }
-        }
-    }
-}
+                let mut conn_entity = commands.spawn(bundle);
 
-fn accept_streams(
-    mut commands: Commands,
-    connection_query: Query<(Entity, &mut QuicConnection), With<QuicServerMarker>>,
-) {
-    for (connection_entity, mut connection) in connection_query {
-        if connection.should_poll_accept() {
-            handle_stream_accept(&mut commands, connection_entity, &mut connection);
-        }
-    }
-}
-
-#[tracing::instrument(name = "accept_server_stream", skip_all, fields(id = %connection.id()))]
-fn handle_stream_accept(
-    commands: &mut Commands,
-    connection_entity: Entity,
-    connection: &mut QuicConnection,
-) {
-    match connection.accept_stream() {
-        Ok(peer_attempt) => {
-            commands.entity(connection_entity).with_children(|parent| {
-                parent.spawn((peer_attempt, QuicServerMarker));
-            });
-        }
-        Err(err) => {
-            error!("Error accepting stream from connection: {}", err);
+                if let Some(identity) = peer_identity {
+                    conn_entity.insert(identity);
+                }
+            }
         }
     }
 }
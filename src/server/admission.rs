@@ -0,0 +1,453 @@
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Weak},
+};
+use thiserror::Error as ThisError;
+
+use crate::common::error_code::QuicErrorCode;
+
+type AdmissionFilter =
+    dyn Fn(&QuicAdmissionRequest) -> Result<(), QuicErrorCode> + Send + Sync;
+
+/// Decides which incoming connections a [QuicServer][crate::server::QuicServer] accepts.
+///
+/// Connections are checked as soon as their handshake completes, before they become an
+/// entity. Refused connections are closed with an application code so the peer learns why.
+/// The checks run in order: deny list, allow list, connection limits, then the filter.
+///
+/// Admits everything by default.
+///
+/// ```ignore
+/// let policy = QuicAdmissionPolicy::default()
+///     .with_max_connections(64)
+///     .with_max_connections_per_ip(4)
+///     .with_denied("10.0.0.0/8".parse()?)
+///     .with_filter(|request| match request.server_name.as_deref() {
+///         Some("game.example.com") => Ok(()),
+///         _ => Err(QuicErrorCode::CONNECTION_REFUSED),
+///     });
+/// ```
+#[derive(Clone, Default)]
+pub struct QuicAdmissionPolicy {
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    allowed: Vec<IpCidr>,
+    denied: Vec<IpCidr>,
+    filter: Option<Arc<AdmissionFilter>>,
+}
+
+impl fmt::Debug for QuicAdmissionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuicAdmissionPolicy")
+            .field("max_connections", &self.max_connections)
+            .field("max_connections_per_ip", &self.max_connections_per_ip)
+            .field("allowed", &self.allowed)
+            .field("denied", &self.denied)
+            .field("filter", &self.filter.is_some())
+            .finish()
+    }
+}
+
+impl QuicAdmissionPolicy {
+    /// Refuses connections with [QuicErrorCode::SERVER_FULL] once the server has this many.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Refuses connections with [QuicErrorCode::SERVER_FULL] once the server has this many
+    /// from the same IP address.
+    pub fn with_max_connections_per_ip(mut self, max_connections: usize) -> Self {
+        self.max_connections_per_ip = Some(max_connections);
+        self
+    }
+
+    /// Only admits addresses in the given range. Can be called multiple times, addresses
+    /// in any of the ranges are admitted. Everything is allowed if no range is added.
+    pub fn with_allowed(mut self, range: IpCidr) -> Self {
+        self.allowed.push(range);
+        self
+    }
+
+    /// Refuses addresses in the given range with [QuicErrorCode::CONNECTION_REFUSED],
+    /// takes priority over [with_allowed][Self::with_allowed()].
+    pub fn with_denied(mut self, range: IpCidr) -> Self {
+        self.denied.push(range);
+        self
+    }
+
+    /// Runs the filter on every connection which passed the other checks,
+    /// returning an error refuses the connection with that code.
+    pub fn with_filter(
+        mut self,
+        filter: impl Fn(&QuicAdmissionRequest) -> Result<(), QuicErrorCode>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    pub fn max_connections_per_ip(&self) -> Option<usize> {
+        self.max_connections_per_ip
+    }
+
+    /// Checks the request against the policy, returning the code to close the connection
+    /// with if it's refused.
+    pub fn check(&self, request: &QuicAdmissionRequest) -> Result<(), QuicErrorCode> {
+        let ip = request.remote_addr.ip().to_canonical();
+
+        if self.denied.iter().any(|range| range.contains(ip)) {
+            return Err(QuicErrorCode::CONNECTION_REFUSED);
+        }
+
+        if !self.allowed.is_empty()
+            && !self.allowed.iter().any(|range| range.contains(ip))
+        {
+            return Err(QuicErrorCode::CONNECTION_REFUSED);
+        }
+
+        if self
+            .max_connections
+            .is_some_and(|max| request.connections >= max)
+        {
+            return Err(QuicErrorCode::SERVER_FULL);
+        }
+
+        if self
+            .max_connections_per_ip
+            .is_some_and(|max| request.connections_from_ip >= max)
+        {
+            return Err(QuicErrorCode::SERVER_FULL);
+        }
+
+        match &self.filter {
+            Some(filter) => filter(request),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An incoming connection waiting to be admitted, see [QuicAdmissionPolicy].
+#[derive(Debug, Clone)]
+pub struct QuicAdmissionRequest {
+    pub remote_addr: SocketAddr,
+    /// The server name the client asked for, if it sent one.
+    pub server_name: Option<String>,
+    /// How many connections the server currently has.
+    pub connections: usize,
+    /// How many connections the server currently has from the same IP address.
+    pub connections_from_ip: usize,
+}
+
+/// A range of IP addresses in CIDR notation, e.g. `192.168.0.0/16` or `fd00::/8`.
+///
+/// IPv4 addresses mapped into IPv6 are matched as IPv4 addresses, a mapped range such as
+/// `::ffff:10.0.0.0/104` is the same as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, IpCidrError> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_len {
+            return Err(IpCidrError::PrefixTooLong(prefix_len));
+        }
+
+        // Mapped ranges which only cover mapped addresses become IPv4 ranges
+        let (addr, prefix_len) = match addr {
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) if prefix_len >= 96 => (IpAddr::V4(v4), prefix_len - 96),
+                _ => (addr, prefix_len),
+            },
+            IpAddr::V4(_) => (addr, prefix_len),
+        };
+
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Returns `true` if the address is in this range.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match self.addr {
+            IpAddr::V4(range) => match addr.to_canonical() {
+                IpAddr::V4(addr) => prefix_matches(
+                    range.to_bits().into(),
+                    addr.to_bits().into(),
+                    self.prefix_len,
+                    32,
+                ),
+                IpAddr::V6(_) => false,
+            },
+            IpAddr::V6(range) => {
+                let addr = match addr {
+                    IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+                    IpAddr::V6(addr) => addr,
+                };
+
+                prefix_matches(range.to_bits(), addr.to_bits(), self.prefix_len, 128)
+            }
+        }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+}
+
+fn prefix_matches(range: u128, addr: u128, prefix_len: u8, bits: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+
+    let shift = (bits - prefix_len) as u32;
+    range >> shift == addr >> shift
+}
+
+impl FromStr for IpCidr {
+    type Err = IpCidrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((addr, prefix_len)) = s.split_once('/') else {
+            // A single address
+            let addr: IpAddr = s.parse().map_err(|_| IpCidrError::InvalidAddress)?;
+            let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+            return Self::new(addr, prefix_len);
+        };
+
+        let addr = addr.parse().map_err(|_| IpCidrError::InvalidAddress)?;
+        let prefix_len = prefix_len.parse().map_err(|_| IpCidrError::InvalidPrefix)?;
+
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+pub enum IpCidrError {
+    #[error("Invalid IP address in CIDR range")]
+    InvalidAddress,
+    #[error("Invalid prefix length in CIDR range")]
+    InvalidPrefix,
+    #[error("Prefix length {0} is longer than the address")]
+    PrefixTooLong(u8),
+}
+
+/// The connections a server has admitted, each is counted until its
+/// [QuicConnection][crate::common::connection::QuicConnection] is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct AdmittedConnections {
+    connections: Vec<(IpAddr, Weak<()>)>,
+}
+
+impl AdmittedConnections {
+    /// Builds the request for a new connection, forgetting any connections which are gone.
+    pub(crate) fn request(
+        &mut self,
+        remote_addr: SocketAddr,
+        server_name: Option<String>,
+    ) -> QuicAdmissionRequest {
+        self.connections
+            .retain(|(_, ticket)| ticket.strong_count() > 0);
+
+        let ip = remote_addr.ip().to_canonical();
+        let connections_from_ip = self
+            .connections
+            .iter()
+            .filter(|(admitted_ip, _)| *admitted_ip == ip)
+            .count();
+
+        QuicAdmissionRequest {
+            remote_addr,
+            server_name,
+            connections: self.connections.len(),
+            connections_from_ip,
+        }
+    }
+
+    /// Counts a new connection for as long as the returned ticket is alive.
+    pub(crate) fn admit(&mut self, remote_addr: SocketAddr) -> Arc<()> {
+        let ticket = Arc::new(());
+        self.connections
+            .push((remote_addr.ip().to_canonical(), Arc::downgrade(&ticket)));
+        ticket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(range: &str) -> IpCidr {
+        range.parse().unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn request(addr: &str, connections: usize, from_ip: usize) -> QuicAdmissionRequest {
+        QuicAdmissionRequest {
+            remote_addr: SocketAddr::new(ip(addr), 4433),
+            server_name: None,
+            connections,
+            connections_from_ip: from_ip,
+        }
+    }
+
+    #[test]
+    fn parses_ranges_and_single_addresses() {
+        assert_eq!(cidr("10.0.0.0/8").to_string(), "10.0.0.0/8");
+        assert_eq!(cidr("192.168.1.5").prefix_len(), 32);
+        assert_eq!(cidr("fd00::1").prefix_len(), 128);
+        assert_eq!(cidr("fd00::/8").to_string(), "fd00::/8");
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        let parse = |range: &str| range.parse::<IpCidr>();
+
+        assert_eq!(parse("10.0.0.0/33"), Err(IpCidrError::PrefixTooLong(33)));
+        assert_eq!(parse("fd00::/129"), Err(IpCidrError::PrefixTooLong(129)));
+        assert_eq!(parse("10.0.0/8"), Err(IpCidrError::InvalidAddress));
+        assert_eq!(parse("10.0.0.0/x"), Err(IpCidrError::InvalidPrefix));
+    }
+
+    #[test]
+    fn matches_by_prefix() {
+        let range = cidr("10.0.0.0/8");
+        assert!(range.contains(ip("10.255.0.1")));
+        assert!(!range.contains(ip("11.0.0.1")));
+
+        let range = cidr("fd00::/8");
+        assert!(range.contains(ip("fd12::1")));
+        assert!(!range.contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn zero_prefix_matches_its_whole_family() {
+        let range = cidr("0.0.0.0/0");
+        assert!(range.contains(ip("1.2.3.4")));
+        assert!(range.contains(ip("::ffff:1.2.3.4")));
+        assert!(!range.contains(ip("2001:db8::1")));
+
+        let range = cidr("::/0");
+        assert!(range.contains(ip("2001:db8::1")));
+        assert!(range.contains(ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn full_prefix_matches_one_address() {
+        let range = cidr("192.168.1.5/32");
+        assert!(range.contains(ip("192.168.1.5")));
+        assert!(!range.contains(ip("192.168.1.4")));
+
+        let range = cidr("2001:db8::5/128");
+        assert!(range.contains(ip("2001:db8::5")));
+        assert!(!range.contains(ip("2001:db8::4")));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_as_ipv4() {
+        assert!(cidr("10.0.0.0/8").contains(ip("::ffff:10.0.0.1")));
+        assert_eq!(cidr("::ffff:10.0.0.0/104"), cidr("10.0.0.0/8"));
+        assert_eq!(cidr("::ffff:10.0.0.1"), cidr("10.0.0.1/32"));
+
+        // Wider than the mapped space, stays an IPv6 range
+        let range = cidr("::ffff:0:0/95");
+        assert!(range.contains(ip("10.0.0.1")));
+        assert!(!range.contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn deny_list_wins_over_allow_list() {
+        let policy = QuicAdmissionPolicy::default()
+            .with_allowed(cidr("10.0.0.0/8"))
+            .with_denied(cidr("10.0.0.5"));
+
+        assert_eq!(policy.check(&request("10.0.0.6", 0, 0)), Ok(()));
+        assert_eq!(
+            policy.check(&request("10.0.0.5", 0, 0)),
+            Err(QuicErrorCode::CONNECTION_REFUSED)
+        );
+        assert_eq!(
+            policy.check(&request("11.0.0.1", 0, 0)),
+            Err(QuicErrorCode::CONNECTION_REFUSED)
+        );
+    }
+
+    #[test]
+    fn limits_refuse_with_server_full() {
+        let policy = QuicAdmissionPolicy::default()
+            .with_max_connections(4)
+            .with_max_connections_per_ip(2);
+
+        assert_eq!(policy.check(&request("10.0.0.1", 3, 1)), Ok(()));
+        assert_eq!(
+            policy.check(&request("10.0.0.1", 4, 0)),
+            Err(QuicErrorCode::SERVER_FULL)
+        );
+        assert_eq!(
+            policy.check(&request("10.0.0.1", 2, 2)),
+            Err(QuicErrorCode::SERVER_FULL)
+        );
+    }
+
+    #[test]
+    fn filter_runs_after_the_other_checks() {
+        let policy = QuicAdmissionPolicy::default()
+            .with_denied(cidr("10.0.0.0/8"))
+            .with_filter(|_| Err(QuicErrorCode::KICKED));
+
+        assert_eq!(
+            policy.check(&request("10.0.0.1", 0, 0)),
+            Err(QuicErrorCode::CONNECTION_REFUSED)
+        );
+        assert_eq!(
+            policy.check(&request("11.0.0.1", 0, 0)),
+            Err(QuicErrorCode::KICKED)
+        );
+    }
+
+    #[test]
+    fn counts_connections_per_ip_until_dropped() {
+        let mut admitted = AdmittedConnections::default();
+        let addr = |addr: &str| SocketAddr::new(ip(addr), 4433);
+
+        let first = admitted.admit(addr("10.0.0.1"));
+        let _second = admitted.admit(addr("::ffff:10.0.0.1"));
+        let _other = admitted.admit(addr("10.0.0.2"));
+
+        let request = admitted.request(addr("10.0.0.1"), None);
+        assert_eq!(request.connections, 3);
+        assert_eq!(request.connections_from_ip, 2);
+
+        drop(first);
+
+        let request = admitted.request(addr("10.0.0.1"), None);
+        assert_eq!(request.connections, 2);
+        assert_eq!(request.connections_from_ip, 1);
+    }
+}
@@ -0,0 +1,141 @@
+use std::net::SocketAddr;
+
+use s2n_quic_tls::{
+    certificate::{Certificate, IntoCertificate, IntoPrivateKey, PrivateKey},
+    error::Error as TlsError,
+};
+
+use crate::common::config::QuicTransportConfig;
+
+/// The configuration used to create a [QuicServer][crate::server::QuicServer]
+/// via [QuicServer::with_config][crate::server::QuicServer::with_config()].
+///
+/// ```no_run
+/// # use bevy_s2n_quic::common::config::{QuicCongestionController, QuicTransportConfig};
+/// # use bevy_s2n_quic::server::config::QuicServerConfig;
+/// # use std::{path::Path, time::Duration};
+/// let config = QuicServerConfig::new(
+///     "0.0.0.0:7777".parse().unwrap(),
+///     Path::new("cert.pem"),
+///     Path::new("key.pem"),
+/// )
+/// .expect("Invalid certificate")
+/// .with_transport(
+///     QuicTransportConfig::default()
+///         .with_application_protocols(["my-game/1"])
+///         .with_max_idle_timeout(Duration::from_secs(10))
+///         .with_congestion_controller(QuicCongestionController::Bbr),
+/// )
+/// .with_max_concurrent_handshakes(256)
+/// .with_client_authentication()
+/// .with_trusted_certificate(Path::new("client-ca.pem"))
+/// .expect("Invalid certificate");
+/// ```
+pub struct QuicServerConfig {
+    bind_addr: SocketAddr,
+    certificate: Certificate,
+    private_key: PrivateKey,
+    transport: QuicTransportConfig,
+    max_concurrent_handshakes: Option<usize>,
+    trusted_certificates: Vec<Certificate>,
+    client_authentication: bool,
+}
+
+impl QuicServerConfig {
+    /// Creates a new server config which will bind to the given address with the given certificates.
+    ///
+    /// QUIC requires some form of TLS certificate, this function accepts the same kinds of certs the regular s2n-quic
+    /// `bind()` function does.
+    pub fn new<C: IntoCertificate, PK: IntoPrivateKey>(
+        bind_addr: SocketAddr,
+        certificate: C,
+        private_key: PK,
+    ) -> Result<Self, TlsError> {
+        Ok(Self {
+            bind_addr,
+            certificate: certificate.into_certificate()?,
+            private_key: private_key.into_private_key()?,
+            transport: QuicTransportConfig::default(),
+            max_concurrent_handshakes: None,
+            trusted_certificates: Vec::new(),
+            client_authentication: false,
+        })
+    }
+
+    /// Sets the transport settings used by every connection of this server.
+    pub fn with_transport(mut self, transport: QuicTransportConfig) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets how many handshakes can be in progress at once before the server
+    /// starts asking new clients to prove their address with a retry.
+    pub fn with_max_concurrent_handshakes(mut self, limit: usize) -> Self {
+        self.max_concurrent_handshakes = Some(limit);
+        self
+    }
+
+    /// Requires every client to present a certificate signed by one of the trusted
+    /// certificates, see [with_trusted_certificate][Self::with_trusted_certificate()].
+    ///
+    /// The verified chain of each client is added to its connection entity as a
+    /// [QuicPeerIdentity][crate::common::connection::identity::QuicPeerIdentity].
+    /// Host names in client certificates are not checked, it's up to the
+    /// application to decide what a given identity is allowed to do.
+    pub fn with_client_authentication(mut self) -> Self {
+        self.client_authentication = true;
+        self
+    }
+
+    /// Trusts the given certificate when verifying client certificates. Can be called
+    /// multiple times to trust multiple certificates.
+    ///
+    /// Once any certificate is trusted the system trust store is no longer used,
+    /// so only clients signed by these certificates will be accepted.
+    pub fn with_trusted_certificate<C: IntoCertificate>(
+        mut self,
+        certificate: C,
+    ) -> Result<Self, TlsError> {
+        self.trusted_certificates
+            .push(certificate.into_certificate()?);
+        Ok(self)
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        self.bind_addr
+    }
+
+    pub fn transport(&self) -> &QuicTransportConfig {
+        &self.transport
+    }
+
+    pub fn max_concurrent_handshakes(&self) -> Option<usize> {
+        self.max_concurrent_handshakes
+    }
+
+    pub fn client_authentication(&self) -> bool {
+        self.client_authentication
+    }
+
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        SocketAddr,
+        Certificate,
+        PrivateKey,
+        QuicTransportConfig,
+        Option<usize>,
+        Vec<Certificate>,
+        bool,
+    ) {
+        (
+            self.bind_addr,
+            self.certificate,
+            self.private_key,
+            self.transport,
+            self.max_concurrent_handshakes,
+            self.trusted_certificates,
+            self.client_authentication,
+        )
+    }
+}
@@ -1,19 +1,31 @@
-use std::{error::Error, net::SocketAddr, sync::Arc};
+use std::{error::Error, net::SocketAddr, sync::Arc, time::Duration};
 
 use bevy::ecs::component::Component;
-use s2n_quic::Server;
-use s2n_quic_tls::certificate::{IntoCertificate, IntoPrivateKey};
+use s2n_quic::{Server, provider::endpoint_limits};
+use s2n_quic_tls::{
+    callbacks::VerifyHostNameCallback,
+    certificate::{IntoCertificate, IntoPrivateKey},
+};
 use tokio::{runtime::Handle, task::JoinError};
 
 use crate::{
     common::{
-        QuicParentId, QuicParentType, connection::QuicConnection, runtime::TokioRuntime,
+        QuicParentId, QuicParentType, config::start_endpoint, connection::QuicConnection,
+        error_code::QuicErrorCode, runtime::TokioRuntime,
+    },
+    server::{
+        admission::{AdmittedConnections, QuicAdmissionPolicy},
+        config::QuicServerConfig,
+        marker::QuicServerMarker,
+        shutdown::ServerShutdown,
     },
-    server::marker::QuicServerMarker,
 };
 
 pub mod acceptor;
+pub mod admission;
+pub mod config;
 pub mod marker;
+pub mod shutdown;
 
 /// The component which manages an instance of a QuicServer.
 ///
@@ -24,6 +36,9 @@ pub struct QuicServer {
     runtime: Handle,
     server: Server,
     id: QuicParentId,
+    shutdown: Option<ServerShutdown>,
+    admission_policy: QuicAdmissionPolicy,
+    admitted: AdmittedConnections,
 }
 
 impl QuicServer {
@@ -31,23 +46,56 @@ impl QuicServer {
     ///
     /// QUIC requires some form of TLS certificate, this function accepts the same kinds of certs the regular s2n-quic
     /// `bind()` function does.
+    ///
+    /// All other settings use their defaults, see [QuicServer::with_config] for more control.
     pub fn bind<C: IntoCertificate, PK: IntoPrivateKey>(
         runtime: &TokioRuntime,
         bind_ip: SocketAddr,
         certificate: C,
         private_key: PK,
+    ) -> Result<Self, Box<dyn Error>> {
+        let config = QuicServerConfig::new(bind_ip, certificate, private_key)?;
+        Self::with_config(runtime, config)
+    }
+
+    /// Creates a new QuicServer from the given [QuicServerConfig].
+    pub fn with_config(
+        runtime: &TokioRuntime,
+        config: QuicServerConfig,
     ) -> Result<Self, Box<dyn Error>> {
         let handle = runtime.handle().clone();
-        let server = runtime.block_on(build_server(bind_ip, certificate, private_key))?;
+        let server = runtime.block_on(build_server(config))?;
 
         Ok(Self {
             runtime: handle,
             server,
             id: QuicParentId::generate_unique(QuicParentType::Server),
+            shutdown: None,
+            admission_policy: QuicAdmissionPolicy::default(),
+            admitted: AdmittedConnections::default(),
         })
     }
 
-    /// Polls to receive any new pending connections
+    /// Sets the [QuicAdmissionPolicy] which decides what connections are accepted.
+    pub fn with_admission_policy(mut self, policy: QuicAdmissionPolicy) -> Self {
+        self.set_admission_policy(policy);
+        self
+    }
+
+    /// Replaces the [QuicAdmissionPolicy], only affects connections accepted from now on.
+    pub fn set_admission_policy(&mut self, policy: QuicAdmissionPolicy) {
+        self.admission_policy = policy;
+    }
+
+    pub fn admission_policy(&self) -> &QuicAdmissionPolicy {
+        &self.admission_policy
+    }
+
+    /// Polls to receive any new pending connections.
+    ///
+    /// New connections are checked against the [QuicAdmissionPolicy] first, refused connections
+    /// are closed straight away and returned as [ConnectionPoll::Rejected]. Once the server is
+    /// shutting down every new connection is refused with the shutdown code.
     pub fn accept_connection(&mut self) -> Result<ConnectionPoll, JoinError> {
         let waker = Arc::new(futures::task::noop_waker_ref());
         let mut cx = std::task::Context::from_waker(&waker);
@@ -57,13 +105,33 @@ impl QuicServer {
         match poll {
             std::task::Poll::Ready(conn_opt) => {
                 if let Some(conn) = conn_opt {
-                    let ret = ConnectionPoll::NewConnection(QuicConnection::new(
-                        self.runtime.clone(),
-                        conn,
-                        self.id,
-                    ));
+                    let Ok(remote_addr) = conn.remote_addr() else {
+                        // The connection is already gone
+                        return Ok(ConnectionPoll::None);
+                    };
+
+                    let server_name = conn
+                        .server_name()
+                        .ok()
+                        .flatten()
+                        .map(|name| name.to_string());
+                    let request = self.admitted.request(remote_addr, server_name);
+
+                    let admission = match &self.shutdown {
+                        Some(shutdown) => Err(shutdown.code()),
+                        None => self.admission_policy.check(&request),
+                    };
+
+                    if let Err(code) = admission {
+                        conn.close(code.into());
+                        return Ok(ConnectionPoll::Rejected { remote_addr, code });
+                    }
+
+                    let mut connection =
+                        QuicConnection::new(self.runtime.clone(), conn, self.id);
+                    connection.set_admission_ticket(self.admitted.admit(remote_addr));
 
-                    Ok(ret)
+                    Ok(ConnectionPoll::NewConnection(connection))
                 } else {
                     bevy::log::info!(
                         "Server connection poll returned none, is our server not running?"
@@ -75,6 +143,34 @@ impl QuicServer {
         }
     }
 
+    /// Gracefully shuts the server down, new connections are refused from now on.
+    ///
+    /// Every send stream of the server's connections is finished first, once they've all been
+    /// flushed, or the grace period has run out, every connection is closed with the given code.
+    /// [QuicServerShutdownComplete][crate::server::shutdown::QuicServerShutdownComplete] is then
+    /// triggered on the server entity, after which it can be despawned.
+    ///
+    /// Does nothing if the server is already shutting down.
+    pub fn shutdown(&mut self, grace_period: Duration, code: QuicErrorCode) {
+        if self.shutdown.is_some() {
+            return;
+        }
+
+        self.shutdown = Some(ServerShutdown::new(grace_period, code));
+    }
+
+    /// Returns `true` once [shutdown][Self::shutdown()] has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    /// Returns `true` once a [shutdown][Self::shutdown()] has closed every connection.
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(ServerShutdown::is_complete)
+    }
+
     pub fn id(&self) -> QuicParentId {
         self.id
     }
@@ -85,21 +181,73 @@ pub enum ConnectionPoll {
     None,
     ServerClosed,
     NewConnection(QuicConnection),
+    /// A connection was refused by the [QuicAdmissionPolicy], or because the server is
+    /// shutting down, and has been closed with the code.
+    Rejected {
+        remote_addr: SocketAddr,
+        code: QuicErrorCode,
+    },
 }
 
-async fn build_server<C: IntoCertificate, PK: IntoPrivateKey>(
-    ip: SocketAddr,
-    certificate: C,
-    private_key: PK,
-) -> Result<Server, Box<dyn Error>> {
-    let tls = s2n_quic_tls::Server::builder()
-        .with_certificate(certificate, private_key)?
-        .build()?;
+async fn build_server(config: QuicServerConfig) -> Result<Server, Box<dyn Error>> {
+    let (
+        bind_addr,
+        certificate,
+        private_key,
+        transport,
+        max_concurrent_handshakes,
+        trusted_certificates,
+        client_authentication,
+    ) = config.into_parts();
 
-    let server = Server::builder().with_tls(tls)?.with_io(ip)?.start()?;
+    let mut tls =
+        s2n_quic_tls::Server::builder().with_certificate(certificate, private_key)?;
+
+    if !trusted_certificates.is_empty() {
+        tls = tls.with_empty_trust_store()?;
+
+        for trusted in trusted_certificates {
+            tls = tls.with_trusted_certificate(trusted)?;
+        }
+    }
+
+    if client_authentication {
+        tls = tls
+            .with_client_authentication()?
+            .with_verify_host_name_callback(AnyClientHostName)?;
+    }
+
+    if !transport.application_protocols().is_empty() {
+        tls = tls.with_application_protocols(transport.application_protocols())?;
+    }
+
+    let mut limits = endpoint_limits::default::Limits::builder();
+
+    if let Some(limit) = max_concurrent_handshakes {
+        limits = limits.with_inflight_handshake_limit(limit)?;
+    }
+
+    let builder = Server::builder()
+        .with_tls(tls.build()?)?
+        .with_io(bind_addr)?
+        .with_limits(transport.build_limits()?)?
+        .with_endpoint_limits(limits.build()?)?
+        .with_event(transport.build_event_subscriber())?;
+
+    let server = start_endpoint!(builder, &transport)?;
     Ok(server)
 }
 
+/// Client certificates are verified against the trust store, but unlike a server
+/// a client has no host name we could check its certificate against.
+struct AnyClientHostName;
+
+impl VerifyHostNameCallback for AnyClientHostName {
+    fn verify_host_name(&self, _host_name: &str) -> bool {
+        true
+    }
+}
+
 pub enum QuitReason {
     ServerClosed,
     BrokenSender,
@@ -0,0 +1,200 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        entity::Entity,
+        event::EntityEvent,
+        hierarchy::Children,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query},
+    },
+    log::{info, tracing, warn},
+};
+use std::time::{Duration, Instant};
+
+use crate::{
+    async_plugin::quic_tick_ready,
+    common::{
+        connection::{QuicConnection, session::QuicConnectionSession},
+        error_code::QuicErrorCode,
+        stream::send::QuicSendStream,
+    },
+    server::QuicServer,
+};
+
+/// Triggered on a server entity once a [shutdown][QuicServer::shutdown()] has closed
+/// every connection of the server.
+#[derive(Debug, Clone, EntityEvent)]
+pub struct QuicServerShutdownComplete {
+    pub entity: Entity,
+    /// The code every connection was closed with.
+    pub code: QuicErrorCode,
+    /// `true` if the grace period ran out before every send stream had been flushed.
+    pub forced: bool,
+    /// How long the shutdown took.
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShutdownPhase {
+    /// Shutdown has been requested, nothing has been closed yet.
+    Requested,
+    /// Every send stream has been asked to finish, waiting on them to be acknowledged.
+    Draining,
+    /// Every connection has been closed with the shutdown code.
+    Closed {
+        forced: bool,
+    },
+    Complete,
+}
+
+/// The progress of a server shutdown, see [QuicServer::shutdown()].
+#[derive(Debug)]
+pub(crate) struct ServerShutdown {
+    code: QuicErrorCode,
+    started_at: Instant,
+    grace_period: Duration,
+    phase: ShutdownPhase,
+}
+
+impl ServerShutdown {
+    pub(crate) fn new(grace_period: Duration, code: QuicErrorCode) -> Self {
+        Self {
+            code,
+            started_at: Instant::now(),
+            grace_period,
+            phase: ShutdownPhase::Requested,
+        }
+    }
+
+    pub(crate) fn code(&self) -> QuicErrorCode {
+        self.code
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        self.phase == ShutdownPhase::Complete
+    }
+
+    fn is_past_grace_period(&self) -> bool {
+        self.started_at.elapsed() >= self.grace_period
+    }
+}
+
+/// The plugin which drives servers through their [shutdown][QuicServer::shutdown()].
+///
+/// Connections are expected to be children of their server, like the
+/// [SimpleServerAcceptorPlugin][crate::server::acceptor::SimpleServerAcceptorPlugin]
+/// spawns them, and streams children of their connection. Connections with a
+/// [QuicConnectionSession] are drained through the session, which needs the
+/// [QuicAeronetPacketPlugin][crate::common::stream::session::QuicAeronetPacketPlugin]
+/// to keep sending while the server shuts down.
+#[derive(Debug)]
+pub struct ServerShutdownPlugin;
+
+impl Plugin for ServerShutdownPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app.add_systems(Update, handle_server_shutdowns.run_if(quic_tick_ready));
+    }
+}
+
+#[tracing::instrument(skip_all)]
+fn handle_server_shutdowns(
+    mut commands: Commands,
+    servers: Query<(Entity, &mut QuicServer, Option<&Children>)>,
+    mut connections: Query<(
+        &QuicConnection,
+        Option<&Children>,
+        Option<&mut QuicConnectionSession>,
+    )>,
+    mut send_streams: Query<&mut QuicSendStream>,
+) {
+    for (entity, mut server, children) in servers {
+        let Some(shutdown) = server.shutdown.as_mut() else {
+            continue;
+        };
+
+        let server_connections: Vec<Entity> = children
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|child| connections.contains(*child))
+            .collect();
+
+        match shutdown.phase {
+            ShutdownPhase::Requested => {
+                info!(
+                    "Shutting down server {} with code {}, grace period {:?}",
+                    entity, shutdown.code, shutdown.grace_period
+                );
+
+                let mut server_connections =
+                    connections.iter_many_mut(&server_connections);
+
+                while let Some((_, streams, session)) = server_connections.fetch_next() {
+                    // Sessions run their streams themselves, without entities
+                    if let Some(mut session) = session {
+                        session.finish_streams();
+                    }
+
+                    for stream in streams.iter().copied().flatten() {
+                        if let Ok(mut send) = send_streams.get_mut(*stream) {
+                            // Finishes the stream once everything queued has been sent
+                            let _ = send.close();
+                        }
+                    }
+                }
+
+                shutdown.phase = ShutdownPhase::Draining;
+            }
+            ShutdownPhase::Draining => {
+                let draining = connections.iter_many(&server_connections).any(
+                    |(_, streams, session)| {
+                        session.is_some_and(|session| session.is_draining())
+                            || streams.iter().copied().flatten().any(|stream| {
+                                send_streams
+                                    .get(*stream)
+                                    .is_ok_and(QuicSendStream::is_open)
+                            })
+                    },
+                );
+
+                if draining && !shutdown.is_past_grace_period() {
+                    continue;
+                }
+
+                let forced = draining;
+
+                if forced {
+                    warn!(
+                        "Server {} didn't finish flushing within {:?}, closing all connections",
+                        entity, shutdown.grace_period
+                    );
+                }
+
+                for (connection, ..) in connections.iter_many(&server_connections) {
+                    connection.close(shutdown.code);
+                }
+
+                shutdown.phase = ShutdownPhase::Closed { forced };
+            }
+            ShutdownPhase::Closed { forced } => {
+                if connections
+                    .iter_many(&server_connections)
+                    .any(|(connection, ..)| connection.is_open())
+                {
+                    continue;
+                }
+
+                info!("Server {} has shut down", entity);
+                shutdown.phase = ShutdownPhase::Complete;
+
+                commands.trigger(QuicServerShutdownComplete {
+                    entity,
+                    code: shutdown.code,
+                    forced,
+                    elapsed: shutdown.started_at.elapsed(),
+                });
+            }
+            ShutdownPhase::Complete => continue,
+        }
+    }
+}
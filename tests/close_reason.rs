@@ -0,0 +1,64 @@
+/// Checks that the reason a connection is disconnected with reaches the peer.
+mod common;
+
+use std::{error::Error, thread, time::Duration};
+
+use aeronet_io::connection::{Disconnect, DisconnectReason, Disconnected};
+use bevy::{app::TaskPoolPlugin, prelude::*};
+use bevy_s2n_quic::{
+    async_plugin::QuicAsyncPlugin,
+    common::{plugin::DisconnectHandlerPlugin, stream::session::QuicAeronetEventPlugin},
+};
+
+use common::{connect, wait_for};
+
+const REASON: &str = "Left the game";
+
+/// Every [Disconnected] event triggered, along with a description of its reason.
+#[derive(Resource, Default)]
+struct DisconnectedLog(Vec<(Entity, String)>);
+
+#[test]
+fn disconnect_reason_reaches_peer() -> Result<(), Box<dyn Error>> {
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        QuicAsyncPlugin::new(0),
+        QuicAeronetEventPlugin,
+        DisconnectHandlerPlugin,
+    ))
+    .init_resource::<DisconnectedLog>()
+    .add_observer(
+        |event: On<Disconnected>, mut log: ResMut<DisconnectedLog>| {
+            let reason = match &event.reason {
+                DisconnectReason::ByUser(reason) => format!("by user: {reason}"),
+                DisconnectReason::ByPeer(reason) => format!("by peer: {reason}"),
+                DisconnectReason::ByError(err) => format!("by error: {err}"),
+            };
+            log.0.push((event.entity, reason));
+        },
+    );
+
+    let (client, server, _endpoints) = connect(&mut app)?;
+
+    // Runs the startup systems, which add the disconnect handler
+    app.update();
+
+    app.world_mut().trigger(Disconnect::new(client, REASON));
+
+    let server_reason = wait_for("the server to disconnect", || {
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+
+        app.world()
+            .resource::<DisconnectedLog>()
+            .0
+            .iter()
+            .find(|(entity, _)| *entity == server)
+            .map(|(_, reason)| reason.clone())
+    });
+
+    assert_eq!(server_reason, format!("by peer: {REASON}"));
+
+    Ok(())
+}
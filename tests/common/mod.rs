@@ -0,0 +1,125 @@
+//! Fixtures shared by the integration tests, each test only uses some of them.
+#![allow(dead_code)]
+
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_s2n_quic::common::{
+    QuicParentId, QuicParentType, connection::QuicConnection, runtime::TokioRuntime,
+};
+use s2n_quic::{
+    Client, Connection, Server, client::Connect, provider::tls::default as tls,
+};
+
+/// How long any step of a test may take before it's considered stuck.
+pub const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A self-signed certificate for `localhost`, generated for each test.
+pub struct TestCertificate {
+    pub cert: String,
+    pub key: String,
+}
+
+impl TestCertificate {
+    pub fn generate() -> Result<Self, Box<dyn Error>> {
+        let identity = rcgen::generate_simple_self_signed(["localhost".to_string()])?;
+
+        Ok(Self {
+            cert: identity.cert.pem(),
+            key: identity.signing_key.serialize_pem(),
+        })
+    }
+}
+
+/// Keeps the endpoints of the connections running.
+pub struct Endpoints {
+    pub server: Server,
+    pub client: Client,
+}
+
+/// Starts an s2n-quic server on a free local port, has to be called within a tokio runtime.
+pub fn start_server(certificate: &TestCertificate) -> Result<Server, Box<dyn Error>> {
+    let server_tls = tls::Server::builder()
+        .with_certificate(certificate.cert.as_str(), certificate.key.as_str())?
+        .build()?;
+
+    Ok(Server::builder()
+        .with_tls(server_tls)?
+        .with_io("127.0.0.1:0")?
+        .start()?)
+}
+
+/// Starts an s2n-quic client trusting the certificate, has to be called within a tokio runtime.
+pub fn start_client(certificate: &TestCertificate) -> Result<Client, Box<dyn Error>> {
+    let client_tls = tls::Client::builder()
+        .with_certificate(certificate.cert.as_str())?
+        .build()?;
+
+    Ok(Client::builder()
+        .with_tls(client_tls)?
+        .with_io("127.0.0.1:0")?
+        .start()?)
+}
+
+/// Connects an s2n-quic client and server, returning the client and server connections.
+pub async fn connect_raw() -> Result<(Connection, Connection, Endpoints), Box<dyn Error>>
+{
+    let certificate = TestCertificate::generate()?;
+    let mut server = start_server(&certificate)?;
+    let client = start_client(&certificate)?;
+
+    let connect = Connect::new(server.local_addr()?).with_server_name("localhost");
+    let (client_connection, server_connection) =
+        tokio::join!(client.connect(connect), server.accept());
+
+    Ok((
+        client_connection?,
+        server_connection.ok_or("Server closed")?,
+        Endpoints { server, client },
+    ))
+}
+
+/// Connects a client and a server, returning the client and server ends as
+/// [QuicConnection] entities.
+pub fn connect(app: &mut App) -> Result<(Entity, Entity, Endpoints), Box<dyn Error>> {
+    let handle = app.world().resource::<TokioRuntime>().handle().clone();
+    let (client_connection, server_connection, endpoints) =
+        handle.block_on(connect_raw())?;
+
+    let client = QuicConnection::new(
+        handle.clone(),
+        client_connection,
+        QuicParentId::generate_unique(QuicParentType::Client),
+    );
+    let server = QuicConnection::new(
+        handle,
+        server_connection,
+        QuicParentId::generate_unique(QuicParentType::Server),
+    );
+
+    let client = app.world_mut().spawn(client).id();
+    let server = app.world_mut().spawn(server).id();
+
+    Ok((client, server, endpoints))
+}
+
+/// Polls until the closure returns something, failing the test once [STEP_TIMEOUT] runs out.
+pub fn wait_for<T>(what: &str, mut poll: impl FnMut() -> Option<T>) -> T {
+    let started_at = Instant::now();
+
+    loop {
+        if let Some(value) = poll() {
+            return value;
+        }
+
+        assert!(
+            started_at.elapsed() < STEP_TIMEOUT,
+            "Timed out waiting for {what}"
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
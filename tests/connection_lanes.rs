@@ -0,0 +1,182 @@
+/// Checks that messages sent on a lane of a connection session arrive on the same lane
+/// of the peer, apart from the packets of the session itself.
+mod common;
+
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+use aeronet_io::Session;
+use bevy::{app::TaskPoolPlugin, prelude::*};
+use bevy_s2n_quic::{
+    async_plugin::QuicAsyncPlugin,
+    common::{
+        connection::{
+            lane::{QuicLaneConfig, QuicLaneKind},
+            session::QuicConnectionSession,
+        },
+        stream::session::QuicAeronetPacketPlugin,
+    },
+};
+use bytes::Bytes;
+
+use common::{Endpoints, STEP_TIMEOUT, connect};
+
+const ORDERED: usize = 0;
+const UNORDERED: usize = 1;
+const SEQUENCED: usize = 2;
+
+fn lane_config() -> QuicLaneConfig {
+    QuicLaneConfig::new([
+        QuicLaneKind::ReliableOrdered,
+        QuicLaneKind::ReliableUnordered,
+        QuicLaneKind::UnreliableSequenced,
+    ])
+}
+
+/// Connects a client and a server, both running a [QuicConnectionSession] with the lanes
+/// of [lane_config()].
+fn connect_sessions(
+    app: &mut App,
+) -> Result<(Entity, Entity, Endpoints), Box<dyn Error>> {
+    let (client, server, endpoints) = connect(app)?;
+
+    for entity in [client, server] {
+        app.world_mut()
+            .entity_mut(entity)
+            .insert((QuicConnectionSession::default(), lane_config()));
+    }
+
+    Ok((client, server, endpoints))
+}
+
+#[test]
+fn lane_messages_arrive_on_their_lane() -> Result<(), Box<dyn Error>> {
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        QuicAsyncPlugin::new(0),
+        QuicAeronetPacketPlugin,
+    ));
+
+    let (client, server, _endpoints) = connect_sessions(&mut app)?;
+
+    let mut client_entity = app.world_mut().entity_mut(client);
+    client_entity
+        .get_mut::<Session>()
+        .unwrap()
+        .send
+        .push(Bytes::from_static(b"session"));
+
+    let mut session = client_entity.get_mut::<QuicConnectionSession>().unwrap();
+    session.send_on_lane(ORDERED, Bytes::from_static(b"first"));
+    session.send_on_lane(ORDERED, Bytes::from_static(b"second"));
+    session.send_on_lane(UNORDERED, Bytes::from_static(b"unordered"));
+    session.send_on_lane(SEQUENCED, Bytes::from_static(b"sequenced"));
+
+    let mut session_packets = Vec::new();
+    let mut lane_messages = Vec::new();
+    let started_at = Instant::now();
+
+    while session_packets.is_empty() || lane_messages.len() < 4 {
+        assert!(
+            started_at.elapsed() < STEP_TIMEOUT,
+            "Timed out waiting for the messages, got {session_packets:?} and {lane_messages:?}"
+        );
+
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+
+        let mut server_entity = app.world_mut().entity_mut(server);
+
+        session_packets.extend(
+            server_entity
+                .get_mut::<Session>()
+                .unwrap()
+                .recv
+                .drain(..)
+                .map(|packet| packet.payload),
+        );
+
+        lane_messages.extend(
+            server_entity
+                .get_mut::<QuicConnectionSession>()
+                .unwrap()
+                .drain_lane_messages()
+                .map(|(lane, packet)| (lane, packet.payload)),
+        );
+    }
+
+    assert_eq!(session_packets, [Bytes::from_static(b"session")]);
+
+    let ordered = lane_messages
+        .iter()
+        .filter(|(lane, _)| *lane == ORDERED)
+        .map(|(_, payload)| payload.clone())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        ordered,
+        [Bytes::from_static(b"first"), Bytes::from_static(b"second")]
+    );
+    assert!(lane_messages.contains(&(UNORDERED, Bytes::from_static(b"unordered"))));
+    assert!(lane_messages.contains(&(SEQUENCED, Bytes::from_static(b"sequenced"))));
+
+    Ok(())
+}
+
+#[test]
+fn ordered_lane_keeps_bursts() -> Result<(), Box<dyn Error>> {
+    /// More messages than the outbound channel of a stream holds at once
+    const BURST: usize = 2000;
+
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        QuicAsyncPlugin::new(0),
+        QuicAeronetPacketPlugin,
+    ));
+
+    let (client, server, _endpoints) = connect_sessions(&mut app)?;
+
+    let mut session = app
+        .world_mut()
+        .get_mut::<QuicConnectionSession>(client)
+        .unwrap();
+
+    for i in 0..BURST {
+        session.send_on_lane(ORDERED, Bytes::from((i as u32).to_be_bytes().to_vec()));
+    }
+
+    let mut received = Vec::new();
+    let started_at = Instant::now();
+
+    while received.len() < BURST {
+        assert!(
+            started_at.elapsed() < STEP_TIMEOUT,
+            "Timed out waiting for the burst, got {} messages",
+            received.len()
+        );
+
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+
+        received.extend(
+            app.world_mut()
+                .get_mut::<QuicConnectionSession>(server)
+                .unwrap()
+                .drain_lane_messages()
+                .map(|(_, packet)| packet.payload),
+        );
+    }
+
+    let expected = (0..BURST)
+        .map(|i| Bytes::from((i as u32).to_be_bytes().to_vec()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(received, expected);
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+/// Checks that reading from a stream which has nothing to read returns straight away,
+/// as the Aeronet packet system reads every stream once per frame.
+mod common;
+
+use std::{error::Error, sync::mpsc, thread, time::Duration};
+
+use bevy_s2n_quic::common::{
+    QuicParentId, QuicParentType, orchestrator::AsyncOrchestrator,
+    stream::receive::QuicReceiveStream,
+};
+use s2n_quic::{Connection, stream::SendStream};
+use tokio::runtime::Runtime;
+
+use common::{Endpoints, connect_raw};
+
+/// How long a read may take before we consider the frame stalled.
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Everything which has to stay alive for the stream to stay open.
+struct IdleStream {
+    receive: QuicReceiveStream,
+    _send: SendStream,
+    _client_connection: Connection,
+    _server_connection: Connection,
+    _endpoints: Endpoints,
+}
+
+/// Opens a bidirectional stream from a client to a server which never sends anything back.
+fn open_idle_stream(runtime: &Runtime) -> Result<IdleStream, Box<dyn Error>> {
+    runtime.block_on(async {
+        let (mut client_connection, server_connection, endpoints) = connect_raw().await?;
+
+        let (receive, send) =
+            client_connection.open_bidirectional_stream().await?.split();
+
+        let parent_id = QuicParentId::generate_unique(QuicParentType::Client);
+        let orchestrator = AsyncOrchestrator::new(runtime.handle().clone());
+
+        Ok(IdleStream {
+            receive: QuicReceiveStream::new(&orchestrator, receive, parent_id),
+            _send: send,
+            _client_connection: client_connection,
+            _server_connection: server_connection,
+            _endpoints: endpoints,
+        })
+    })
+}
+
+#[test]
+fn idle_stream_does_not_block() {
+    let runtime = Runtime::new().expect("Unable to start runtime");
+    let mut stream = open_idle_stream(&runtime).expect("Unable to open stream");
+
+    let (sender, receiver) = mpsc::channel();
+
+    // Read on another thread so a regression fails the test rather than hanging it.
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let packets = stream.receive.try_recv_many(&mut buffer, 64);
+        let messages = stream.receive.recv_messages(&mut buffer, 64);
+        let _ = sender.send((packets, messages, stream));
+    });
+
+    let (packets, messages, stream) = receiver
+        .recv_timeout(STALL_TIMEOUT)
+        .expect("Reading an idle stream stalled the caller");
+
+    assert_eq!(packets, 0);
+    assert_eq!(messages, 0);
+    assert!(stream.receive.is_open());
+}
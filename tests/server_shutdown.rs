@@ -0,0 +1,238 @@
+/// Checks that a server shutdown flushes everything queued on its send streams
+/// before the streams are finished and the connections closed.
+mod common;
+
+use std::{
+    error::Error,
+    net::{SocketAddr, UdpSocket},
+};
+
+use aeronet_io::Session;
+use bevy::{app::TaskPoolPlugin, prelude::*};
+use bevy_s2n_quic::{
+    async_plugin::QuicAsyncPlugin,
+    common::{
+        connection::{QuicConnection, session::QuicConnectionSession},
+        error_code::QuicErrorCode,
+        runtime::TokioRuntime,
+        stream::session::QuicAeronetPacketPlugin,
+    },
+    server::{
+        ConnectionPoll, QuicServer,
+        shutdown::{QuicServerShutdownComplete, ServerShutdownPlugin},
+    },
+};
+use bytes::Bytes;
+use s2n_quic::{Client, Connection, client::Connect};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+use common::{STEP_TIMEOUT, TestCertificate, start_client, wait_for};
+
+const MESSAGE_COUNT: usize = 512;
+const MESSAGE_SIZE: usize = 1024;
+/// The first unidirectional stream opened by a server.
+const SERVER_CONTROL_STREAM_ID: u64 = 3;
+/// What a session adds to each packet it sends on a stream: the length prefix of the
+/// message and the tag of packets which aren't on a lane.
+const SESSION_PACKET_OVERHEAD: usize = 2 + 1;
+
+fn free_port() -> Result<SocketAddr, Box<dyn Error>> {
+    Ok(UdpSocket::bind("127.0.0.1:0")?.local_addr()?)
+}
+
+/// Whether the last shutdown ran out of time before everything was flushed.
+#[derive(Resource, Default)]
+struct ShutdownForced(Option<bool>);
+
+fn shutdown_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        QuicAsyncPlugin::new(0),
+        QuicAeronetPacketPlugin,
+        ServerShutdownPlugin,
+    ))
+    .init_resource::<ShutdownForced>()
+    .add_observer(
+        |event: On<QuicServerShutdownComplete>, mut forced: ResMut<ShutdownForced>| {
+            forced.0 = Some(event.forced);
+        },
+    );
+
+    app
+}
+
+/// Starts a [QuicServer] and connects an s2n-quic client to it, returning the server,
+/// the server's end of the connection, the client's end and the client endpoint.
+fn connect_to_server(
+    app: &App,
+) -> Result<(QuicServer, QuicConnection, Connection, Client), Box<dyn Error>> {
+    let certificate = TestCertificate::generate()?;
+
+    let runtime = app.world().resource::<TokioRuntime>();
+    let handle = runtime.handle().clone();
+    let addr = free_port()?;
+    let mut server = QuicServer::bind(
+        runtime,
+        addr,
+        certificate.cert.as_str(),
+        certificate.key.as_str(),
+    )?;
+
+    let client = handle.block_on(async { start_client(&certificate) })?;
+
+    let connect = Connect::new(addr).with_server_name("localhost");
+    let client_connection = handle.spawn(client.connect(connect));
+
+    let connection = wait_for("the connection", || {
+        match server.accept_connection().expect("Server task failed") {
+            ConnectionPoll::NewConnection(connection) => Some(connection),
+            _ => None,
+        }
+    });
+    let client_connection = handle.block_on(client_connection)??;
+
+    Ok((server, connection, client_connection, client))
+}
+
+/// Reads the first stream the server opens after its control stream until it finishes,
+/// returning how many bytes were received.
+fn read_server_stream(
+    handle: &Handle,
+    mut client_connection: Connection,
+) -> JoinHandle<Result<usize, Box<dyn Error + Send + Sync>>> {
+    handle.spawn(async move {
+        let mut stream = client_connection
+            .accept_receive_stream()
+            .await?
+            .ok_or("No stream was opened")?;
+
+        // The server opens its control stream before any other
+        if stream.id() == SERVER_CONTROL_STREAM_ID {
+            stream = client_connection
+                .accept_receive_stream()
+                .await?
+                .ok_or("No stream was opened")?;
+        }
+
+        let mut received = 0;
+
+        while let Some(chunk) = stream.receive().await? {
+            received += chunk.len();
+        }
+
+        Ok(received)
+    })
+}
+
+/// Shuts the server down and waits for it to finish, returning whether it was forced
+/// and how many bytes the reader received.
+fn shut_down(
+    app: &mut App,
+    server_entity: Entity,
+    reader: JoinHandle<Result<usize, Box<dyn Error + Send + Sync>>>,
+) -> Result<(bool, usize), Box<dyn Error>> {
+    wait_for("the shutdown", || {
+        app.update();
+        app.world()
+            .get::<QuicServer>(server_entity)
+            .unwrap()
+            .is_shut_down()
+            .then_some(())
+    });
+
+    let handle = app.world().resource::<TokioRuntime>().handle().clone();
+    let received = handle
+        .block_on(async { tokio::time::timeout(STEP_TIMEOUT, reader).await })??
+        .map_err(|e| e.to_string())?;
+
+    let forced = app
+        .world()
+        .resource::<ShutdownForced>()
+        .0
+        .ok_or("Shutdown didn't complete")?;
+
+    Ok((forced, received))
+}
+
+#[test]
+fn shutdown_flushes_queued_data() -> Result<(), Box<dyn Error>> {
+    let mut app = shutdown_app();
+    let handle = app.world().resource::<TokioRuntime>().handle().clone();
+    let (server, mut connection, client_connection, _client) = connect_to_server(&app)?;
+
+    let mut attempt = connection.open_send_stream()?;
+    let mut send = wait_for("the send stream", || {
+        attempt.attempt_result().ok().flatten()
+    });
+
+    for i in 0..MESSAGE_COUNT {
+        send.send(Bytes::from(vec![i as u8; MESSAGE_SIZE]))?;
+    }
+
+    let server_entity = app.world_mut().spawn(server).id();
+    let connection_entity = app
+        .world_mut()
+        .spawn((connection, ChildOf(server_entity)))
+        .id();
+    app.world_mut().spawn((send, ChildOf(connection_entity)));
+
+    app.world_mut()
+        .get_mut::<QuicServer>(server_entity)
+        .unwrap()
+        .shutdown(STEP_TIMEOUT, QuicErrorCode::NO_ERROR);
+    app.update();
+
+    // Only start reading once the stream is being closed, so the send task is still
+    // held up by flow control with data queued when the close arrives
+    let reader = read_server_stream(&handle, client_connection);
+    let (forced, received) = shut_down(&mut app, server_entity, reader)?;
+
+    assert!(!forced);
+    assert_eq!(received, MESSAGE_COUNT * MESSAGE_SIZE);
+
+    Ok(())
+}
+
+#[test]
+fn shutdown_flushes_sessions() -> Result<(), Box<dyn Error>> {
+    let mut app = shutdown_app();
+    let handle = app.world().resource::<TokioRuntime>().handle().clone();
+    let (server, connection, client_connection, _client) = connect_to_server(&app)?;
+
+    let server_entity = app.world_mut().spawn(server).id();
+    let connection_entity = app
+        .world_mut()
+        .spawn((
+            connection,
+            QuicConnectionSession::default(),
+            ChildOf(server_entity),
+        ))
+        .id();
+
+    let mut session = app
+        .world_mut()
+        .get_mut::<Session>(connection_entity)
+        .unwrap();
+    for i in 0..MESSAGE_COUNT {
+        session.send.push(Bytes::from(vec![i as u8; MESSAGE_SIZE]));
+    }
+
+    // The session has no stream entities, it's drained through the session itself
+    app.world_mut()
+        .get_mut::<QuicServer>(server_entity)
+        .unwrap()
+        .shutdown(STEP_TIMEOUT, QuicErrorCode::NO_ERROR);
+    app.update();
+
+    let reader = read_server_stream(&handle, client_connection);
+    let (forced, received) = shut_down(&mut app, server_entity, reader)?;
+
+    assert!(!forced);
+    assert_eq!(
+        received,
+        MESSAGE_COUNT * (MESSAGE_SIZE + SESSION_PACKET_OVERHEAD)
+    );
+
+    Ok(())
+}
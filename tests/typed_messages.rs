@@ -0,0 +1,75 @@
+#![cfg(feature = "bincode")]
+/// Checks that a message which doesn't decode comes back as an error for that message
+/// alone, without affecting the messages around it.
+mod common;
+
+use std::{error::Error, thread, time::Duration};
+
+use bevy_s2n_quic::common::{
+    QuicParentId, QuicParentType,
+    orchestrator::AsyncOrchestrator,
+    stream::{
+        codec::{BincodeCodec, MessageCodec},
+        receive::QuicReceiveStream,
+        send::QuicSendStream,
+    },
+};
+use bytes::Bytes;
+use tokio::runtime::Runtime;
+
+use common::{connect_raw, wait_for};
+
+type Message = (u32, String);
+
+#[test]
+fn garbage_message_surfaces_as_decode_error() -> Result<(), Box<dyn Error>> {
+    let runtime = Runtime::new()?;
+    let orchestrator = AsyncOrchestrator::new(runtime.handle().clone());
+    let parent_id = QuicParentId::generate_unique(QuicParentType::Client);
+
+    let (mut client_connection, mut server_connection, _endpoints) =
+        runtime.block_on(connect_raw())?;
+
+    let send = runtime.block_on(client_connection.open_send_stream())?;
+    let mut send = QuicSendStream::new(&orchestrator, send, parent_id);
+
+    let first: Message = (1, "first".to_string());
+    let last: Message = (3, "last".to_string());
+    // Not a valid bincode message, the length of the string is cut off
+    let garbage = Bytes::from_static(&[2, 0xff]);
+
+    assert!(BincodeCodec::decode::<Message>(&garbage).is_err());
+
+    send.send_typed::<BincodeCodec, _>(&first)?;
+    send.send_message(garbage.clone())?;
+    send.send_typed::<BincodeCodec, _>(&last)?;
+
+    let receive = runtime
+        .block_on(server_connection.accept_receive_stream())?
+        .ok_or("No stream was opened")?;
+    let mut receive = QuicReceiveStream::new(&orchestrator, receive, parent_id);
+
+    let mut received = Vec::new();
+
+    wait_for("the messages", || {
+        receive.recv_typed::<BincodeCodec, Message>(&mut received, 16);
+        thread::sleep(Duration::from_millis(1));
+        (received.len() >= 3).then_some(())
+    });
+
+    assert_eq!(received.len(), 3);
+    assert_eq!(received[0].as_ref().ok(), Some(&first));
+
+    let err = received[1]
+        .as_ref()
+        .expect_err("Garbage decoded as a message");
+    assert_eq!(err.payload, garbage);
+    assert_eq!(err.stream_id, receive.id());
+
+    assert_eq!(received[2].as_ref().ok(), Some(&last));
+    assert!(receive.is_open());
+
+    drop(send);
+
+    Ok(())
+}